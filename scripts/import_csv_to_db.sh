@@ -161,6 +161,37 @@ if [ ! -f "$CSV_FILE" ]; then
     exit 1
 fi
 
+# 检查 CSV 结构版本（导出时写入的 .meta 边车文件，缺失时视为 v1）
+EXPECTED_SCHEMA_VERSION=1
+META_FILE="$CSV_FILE.meta"
+DELIMITER=","
+NULL_TOKEN='\N'
+# 列顺序与导出时一致（.meta 中的 columns，含注解器扩展列）；缺少 .meta 时为 v1 标准列
+COLUMNS="fbillid, fbuyertaxno, fsalertaxno, fspbm, finvoiceid, finvoiceitemid, fnum, fbillamount, finvoiceamount, fmatchamount, fbillunitprice, fbillqty, finvoiceunitprice, finvoiceqty, fmatchtime"
+if [ -f "$META_FILE" ]; then
+    # 导出时配置的分隔符 (CSV_DELIMITER)，旧 .meta 文件无此字段
+    META_DELIMITER=$(sed -n 's/.*"delimiter"[[:space:]]*:[[:space:]]*"\(.*\)".*/\1/p' "$META_FILE" | head -n 1)
+    if [ "$META_DELIMITER" = '\t' ]; then
+        DELIMITER=$'\t'
+    elif [ -n "$META_DELIMITER" ]; then
+        DELIMITER="$META_DELIMITER"
+    fi
+    # 导出时配置的空值标记 (CSV_NULL_TOKEN)，JSON 中 \N 转义为 \\N；旧 .meta 文件无此字段，按 \N 处理
+    if grep -q '"null_token"' "$META_FILE"; then
+        NULL_TOKEN=$(sed -n 's/.*"null_token"[[:space:]]*:[[:space:]]*"\(.*\)".*/\1/p' "$META_FILE" | head -n 1 | sed 's/\\\\/\\/g')
+    fi
+    META_COLUMNS=$(tr -d '\n' < "$META_FILE" | sed -n 's/.*"columns"[[:space:]]*:[[:space:]]*\[\([^]]*\)\].*/\1/p' | tr -d ' "' | sed 's/,/, /g')
+    if [ -n "$META_COLUMNS" ]; then
+        COLUMNS="$META_COLUMNS"
+    fi
+    SCHEMA_VERSION=$(sed -n 's/.*"schema_version"[[:space:]]*:[[:space:]]*\([0-9][0-9]*\).*/\1/p' "$META_FILE" | head -n 1)
+    if [ "$SCHEMA_VERSION" != "$EXPECTED_SCHEMA_VERSION" ]; then
+        echo "Error: CSV schema version mismatch: $CSV_FILE is v${SCHEMA_VERSION:-unknown}, this importer expects v$EXPECTED_SCHEMA_VERSION"
+        echo "Meta file: $META_FILE"
+        exit 1
+    fi
+fi
+
 # ========== 执行导入 ==========
 
 TABLE_NAME="t_sim_match_result_1201"
@@ -173,6 +204,9 @@ echo "CSV File:    $CSV_FILE"
 echo "Records:     $RECORD_COUNT"
 echo "Database:    $DB_NAME"
 echo "Table:       $TABLE_NAME"
+echo "Delimiter:   $DELIMITER"
+echo "Null token:  '$NULL_TOKEN'"
+echo "Columns:     $COLUMNS"
 echo "========================================"
 
 # 导入前记录数
@@ -181,7 +215,7 @@ echo "Before import: $BEFORE_COUNT records"
 
 # 执行导入
 echo "Importing..."
-psql -h "$DB_HOST" -p "$DB_PORT" -U "$DB_USER" -d "$DB_NAME" -c "\copy $TABLE_NAME (fbillid, fbuyertaxno, fsalertaxno, fspbm, finvoiceid, finvoiceitemid, fnum, fbillamount, finvoiceamount, fmatchamount, fbillunitprice, fbillqty, finvoiceunitprice, finvoiceqty, fmatchtime) FROM '$CSV_FILE' WITH (FORMAT csv, NULL '')"
+psql -h "$DB_HOST" -p "$DB_PORT" -U "$DB_USER" -d "$DB_NAME" -c "\copy $TABLE_NAME ($COLUMNS) FROM '$CSV_FILE' WITH (FORMAT csv, NULL '$NULL_TOKEN', DELIMITER '$DELIMITER')"
 
 # 导入后记录数
 AFTER_COUNT=$(psql -h "$DB_HOST" -p "$DB_PORT" -U "$DB_USER" -d "$DB_NAME" -t -c "SELECT COUNT(*) FROM $TABLE_NAME;" | tr -d ' ')
@@ -0,0 +1,96 @@
+//! 评分上下文基准: 单线程直接使用 `InvoiceScoringContext` 与经 `SharedScoringContext::with_lock` 访问的开销对比
+//!
+//! 运行: cargo bench --bench scoring_context
+
+use bigdecimal::BigDecimal;
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use tax_redflush_rust::config::ZeroAmountPolicy;
+use tax_redflush_rust::models::{
+    InvoiceItemDetail, InvoiceScoringContext, MatchBillItem1201, MatchingRequirements, SharedScoringContext,
+};
+
+const SKU_COUNT: i64 = 20;
+const ITEMS_PER_INVOICE: i64 = 5;
+
+/// 每张发票覆盖 5 个相邻SKU，金额按发票ID错开，避免评分全部相同
+fn invoice_items(invoice_count: i64) -> Vec<InvoiceItemDetail> {
+    (1..=invoice_count)
+        .flat_map(|invoice_id| {
+            (0..ITEMS_PER_INVOICE).map(move |offset| InvoiceItemDetail {
+                invoice_id,
+                item_id: invoice_id * 100 + offset,
+                product_code: format!("SKU{:02}", (invoice_id + offset) % SKU_COUNT),
+                quantity: BigDecimal::from(1),
+                amount: BigDecimal::from(10 + invoice_id % 7),
+                unit_price: None,
+                currency: None,
+                issue_time: None,
+                invoice_total: None,
+            })
+        })
+        .collect()
+}
+
+/// 每个SKU需求 200，需要选中大部分发票才能满足
+fn requirements() -> MatchingRequirements {
+    let bill_items: Vec<MatchBillItem1201> = (0..SKU_COUNT)
+        .map(|sku| MatchBillItem1201 {
+            fid: 1,
+            fentryid: sku,
+            fspbm: format!("SKU{:02}", sku),
+            famount: BigDecimal::from(200),
+            fnum: None,
+            funitprice: None,
+            fcurrency: None,
+        })
+        .collect();
+    MatchingRequirements::from_bill_items(&bill_items, ZeroAmountPolicy::default()).unwrap()
+}
+
+/// 选中一张发票并消费其可用明细，返回是否还有可选发票
+fn select_and_consume(context: &mut InvoiceScoringContext, reqs: &mut MatchingRequirements) -> bool {
+    let Some(invoice_id) = context.find_best_invoice_lazy(reqs) else {
+        return false;
+    };
+    for item in context.get_available_items(invoice_id) {
+        let Some(required) = reqs.get_remaining(&item.product_code).cloned() else {
+            continue;
+        };
+        let amount = if item.remaining_amount < required { item.remaining_amount.clone() } else { required };
+        context.consume_item(invoice_id, &item.product_code, &amount);
+        reqs.reduce(&item.product_code, &amount);
+    }
+    true
+}
+
+fn direct(mut context: InvoiceScoringContext, mut reqs: MatchingRequirements) -> usize {
+    context.init_heap(&reqs);
+    while !reqs.is_satisfied() && select_and_consume(&mut context, &mut reqs) {}
+    context.used_count()
+}
+
+/// 每次选票 + 消费在同一把锁内完成（与并发共享时的用法一致）
+fn shared(context: InvoiceScoringContext, mut reqs: MatchingRequirements) -> usize {
+    let shared = SharedScoringContext::new(context);
+    shared.init_heap(&reqs);
+    while !reqs.is_satisfied() && shared.with_lock(|context| select_and_consume(context, &mut reqs)) {}
+    shared.used_count()
+}
+
+fn bench_scoring_context(c: &mut Criterion) {
+    let mut group = c.benchmark_group("greedy_selection");
+    for invoice_count in [200, 2000] {
+        let items = invoice_items(invoice_count);
+        let setup = || (InvoiceScoringContext::from_items(items.clone()), requirements());
+        group.bench_with_input(BenchmarkId::new("direct", invoice_count), &invoice_count, |b, _| {
+            b.iter_batched(setup, |(context, reqs)| direct(context, reqs), BatchSize::LargeInput)
+        });
+        group.bench_with_input(BenchmarkId::new("with_lock", invoice_count), &invoice_count, |b, _| {
+            b.iter_batched(setup, |(context, reqs)| shared(context, reqs), BatchSize::LargeInput)
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_scoring_context);
+criterion_main!(benches);
@@ -0,0 +1,28 @@
+-- Migration: 匹配统计历史表
+-- 每次匹配（请求开启 persist_stats 时）每个单据写入一行，供看板统计历史运行情况
+--
+-- 表名后缀与服务的 TABLE_SUFFIX 一致，通过 psql 变量传入（未传入时为 _1201）:
+--   psql -v table_suffix=_1201 -f migrations/004_match_stats_table.sql
+-- 创建 t_sim_match_stats{table_suffix} 及索引 idx_match_stats_bill_runtime{table_suffix}
+
+\if :{?table_suffix}
+\else
+\set table_suffix _1201
+\endif
+\set stats_table t_sim_match_stats :table_suffix
+\set stats_index idx_match_stats_bill_runtime :table_suffix
+
+CREATE TABLE IF NOT EXISTS :"stats_table" (
+    fid BIGSERIAL PRIMARY KEY,
+    fbillid BIGINT NOT NULL,            -- 单据ID
+    ftotalskus INTEGER NOT NULL,        -- 需匹配SKU数
+    fmatchedskus INTEGER NOT NULL,      -- 完全满足的SKU数
+    finvoicesused INTEGER NOT NULL,     -- 使用的发票数
+    ftotalmatchedamount NUMERIC(23, 10) NOT NULL,  -- 已匹配金额
+    fcandidateinvoices INTEGER NOT NULL,           -- 候选发票数
+    fruntime TIMESTAMPTZ NOT NULL DEFAULT now()    -- 运行时间
+);
+
+-- 按单据查询历史运行记录
+CREATE INDEX IF NOT EXISTS :"stats_index"
+ON :"stats_table"(fbillid, fruntime DESC);
@@ -0,0 +1,13 @@
+use crate::service::InFlightTracker;
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use std::sync::Arc;
+
+/// 进行中请求登记中间件：请求处理期间计入进行中的匹配，停机时等待其完成
+pub async fn track_in_flight(State(tracker): State<Arc<InFlightTracker>>, request: Request, next: Next) -> Response {
+    let _guard = tracker.enter();
+    next.run(request).await
+}
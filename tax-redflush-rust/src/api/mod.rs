@@ -1,3 +1,9 @@
 pub mod handlers;
+pub mod in_flight;
+pub mod state;
+pub mod timeout;
 
 pub use handlers::*;
+pub use in_flight::track_in_flight;
+pub use state::AppState;
+pub use timeout::{enforce_timeout, RequestTimeout};
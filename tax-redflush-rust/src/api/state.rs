@@ -0,0 +1,67 @@
+use crate::service::{BatchProgress, InFlightTracker, InvoiceCentricMatcher, JobRegistry, MatcherService};
+use axum::extract::FromRef;
+#[cfg(feature = "metrics")]
+use metrics_exporter_prometheus::PrometheusHandle;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+/// 共享状态：包含两种匹配服务
+#[derive(Clone)]
+pub struct AppState {
+    pub sku_centric: Arc<MatcherService>,
+    pub invoice_centric: Arc<InvoiceCentricMatcher>,
+    /// Invoice-Centric 同步批量的进度
+    pub progress: Arc<BatchProgress>,
+    /// Invoice-Centric 异步匹配任务
+    pub jobs: Arc<JobRegistry>,
+    /// 进行中的匹配请求与异步任务（停机时等待其完成）
+    pub in_flight: Arc<InFlightTracker>,
+    /// Prometheus 指标渲染句柄（GET /metrics）
+    #[cfg(feature = "metrics")]
+    pub metrics: PrometheusHandle,
+    /// 数据库连接池（健康检查用）
+    pub pool: PgPool,
+}
+
+impl FromRef<AppState> for Arc<MatcherService> {
+    fn from_ref(state: &AppState) -> Self {
+        state.sku_centric.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<InvoiceCentricMatcher> {
+    fn from_ref(state: &AppState) -> Self {
+        state.invoice_centric.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<BatchProgress> {
+    fn from_ref(state: &AppState) -> Self {
+        state.progress.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<JobRegistry> {
+    fn from_ref(state: &AppState) -> Self {
+        state.jobs.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<InFlightTracker> {
+    fn from_ref(state: &AppState) -> Self {
+        state.in_flight.clone()
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl FromRef<AppState> for PrometheusHandle {
+    fn from_ref(state: &AppState) -> Self {
+        state.metrics.clone()
+    }
+}
+
+impl FromRef<AppState> for PgPool {
+    fn from_ref(state: &AppState) -> Self {
+        state.pool.clone()
+    }
+}
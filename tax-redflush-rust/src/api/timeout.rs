@@ -0,0 +1,163 @@
+use crate::service::CancellationToken;
+use axum::{
+    extract::{Json, Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use std::time::Duration;
+
+/// 超时响应体
+#[derive(Debug, Serialize)]
+pub struct TimeoutResponse {
+    pub success: bool,
+    pub message: String,
+}
+
+/// 匹配请求的取消设置
+#[derive(Debug, Clone)]
+pub struct RequestTimeout {
+    /// 请求时限，None 表示不限时
+    pub limit: Option<Duration>,
+    /// 停机令牌，每个请求的令牌由其派生
+    pub shutdown: CancellationToken,
+}
+
+/// 超时取消后等待处理函数到达检查点的上限，超过时放弃等待直接返回 504
+const CANCEL_DRAIN_LIMIT: Duration = Duration::from_secs(10);
+
+/// 请求取消中间件：为每个请求派生取消令牌（放入请求扩展，处理函数通过
+/// `Extension<CancellationToken>` 取得），超过时限时取消令牌并返回 504
+///
+/// 停机取消 shutdown 时，所有进行中请求的令牌随之取消。匹配在下一个检查点停止，
+/// 已完成单据的结果保留，持有的单据锁等资源随之释放。
+/// 超时时先取消令牌、继续驱动处理函数直到它在检查点停止（清理未完成的输出、汇总部分统计），再返回 504
+pub async fn enforce_timeout(State(timeout): State<RequestTimeout>, mut request: Request, next: Next) -> Response {
+    let cancel = timeout.shutdown.child_token();
+    request.extensions_mut().insert(cancel.clone());
+    let Some(limit) = timeout.limit else {
+        return next.run(request).await;
+    };
+
+    let path = request.uri().path().to_string();
+    let handler = next.run(request);
+    tokio::pin!(handler);
+    match tokio::time::timeout(limit, &mut handler).await {
+        Ok(response) => response,
+        Err(_) => {
+            cancel.cancel();
+            tracing::warn!("请求 {} 超时 (>{:?}), 已取消, 等待匹配在检查点停止", path, limit);
+            if tokio::time::timeout(CANCEL_DRAIN_LIMIT, &mut handler).await.is_err() {
+                tracing::warn!("请求 {} 取消后 {:?} 内未到达检查点, 放弃等待", path, CANCEL_DRAIN_LIMIT);
+            }
+            let response = TimeoutResponse {
+                success: false,
+                message: format!("Request timed out after {}s", limit.as_secs()),
+            };
+            (StatusCode::GATEWAY_TIMEOUT, Json(response)).into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, middleware, routing::post, Extension, Router};
+    use std::sync::{Arc, Mutex};
+    use tower::ServiceExt;
+
+    /// 模拟卡住的匹配: 记下请求令牌，睡眠结束才返回
+    fn slow_router(timeout: RequestTimeout, seen: Arc<Mutex<Option<CancellationToken>>>) -> Router {
+        Router::new()
+            .route(
+                "/api/match/slow",
+                post(move |Extension(cancel): Extension<CancellationToken>| async move {
+                    *seen.lock().unwrap() = Some(cancel);
+                    tokio::time::sleep(Duration::from_millis(200)).await;
+                    "done"
+                }),
+            )
+            .route_layer(middleware::from_fn_with_state(timeout, enforce_timeout))
+    }
+
+    async fn call(router: Router) -> Response {
+        let request = Request::post("/api/match/slow").body(Body::empty()).unwrap();
+        router.oneshot(request).await.unwrap()
+    }
+
+    fn limited(limit: Duration) -> RequestTimeout {
+        RequestTimeout { limit: Some(limit), shutdown: CancellationToken::new() }
+    }
+
+    #[tokio::test]
+    async fn slow_match_yields_504_and_is_cancelled() {
+        let seen = Arc::new(Mutex::new(None));
+
+        let response = call(slow_router(limited(Duration::from_millis(20)), seen.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["success"], false);
+        assert!(seen.lock().unwrap().as_ref().unwrap().is_cancelled());
+    }
+
+    /// 模拟带检查点的流式匹配: 先写出部分结果，每 5ms 检查一次令牌，取消时删除未完成的文件
+    fn checkpointed_router(timeout: RequestTimeout, output: std::path::PathBuf, stopped: Arc<Mutex<bool>>) -> Router {
+        Router::new()
+            .route(
+                "/api/match/slow",
+                post(move |Extension(cancel): Extension<CancellationToken>| async move {
+                    std::fs::write(&output, "partial\n").unwrap();
+                    for _ in 0..200 {
+                        if cancel.is_cancelled() {
+                            std::fs::remove_file(&output).unwrap();
+                            *stopped.lock().unwrap() = true;
+                            return "cancelled";
+                        }
+                        tokio::time::sleep(Duration::from_millis(5)).await;
+                    }
+                    "done"
+                }),
+            )
+            .route_layer(middleware::from_fn_with_state(timeout, enforce_timeout))
+    }
+
+    #[tokio::test]
+    async fn timed_out_match_stops_at_checkpoint_before_504() {
+        let output = std::env::temp_dir().join(format!("redflush_test_timeout_{}.csv", std::process::id()));
+        let stopped = Arc::new(Mutex::new(false));
+
+        let response = call(checkpointed_router(limited(Duration::from_millis(20)), output.clone(), stopped.clone())).await;
+
+        // 返回 504 时匹配已在检查点停止并删除了未完成的输出
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+        assert!(*stopped.lock().unwrap());
+        assert!(!output.exists());
+    }
+
+    #[tokio::test]
+    async fn fast_match_passes_through() {
+        let seen = Arc::new(Mutex::new(None));
+
+        let response = call(slow_router(limited(Duration::from_secs(5)), seen.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!seen.lock().unwrap().as_ref().unwrap().is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn shutdown_cancels_in_flight_request() {
+        let shutdown = CancellationToken::new();
+        let seen = Arc::new(Mutex::new(None));
+        let router = slow_router(RequestTimeout { limit: None, shutdown: shutdown.clone() }, seen.clone());
+
+        let request = tokio::spawn(call(router));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        shutdown.cancel();
+
+        assert!(seen.lock().unwrap().as_ref().unwrap().is_cancelled());
+        assert_eq!(request.await.unwrap().status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,94 @@
+//! 命令行批量匹配（Invoice-Centric），不启动 HTTP 服务
+//!
+//! 用法:
+//!   tax-redflush-match --bills 1001,1002,1003
+//!   tax-redflush-match --file bill_ids.txt   (每行一个ID或单列 CSV，`-` 表示标准输入)
+//!   tax-redflush-match --import output/match_results_xxx.csv   (导入导出的结果 CSV，校验 `.meta` 结构版本)
+//!
+//! 各单据的 MatchStats 以 JSON 数组输出到标准输出，日志输出到标准错误。
+//! 退出码: 0 全部SKU已满足; 1 有单据存在未满足的SKU; 2 参数错误或匹配失败
+
+use std::io::Read;
+use std::path::Path;
+use std::process::ExitCode;
+use tax_redflush_rust::db::{queries, tables};
+use tax_redflush_rust::service::parse_bill_ids;
+use tax_redflush_rust::{create_pool, AppConfig, InvoiceCentricMatcher};
+use tracing_subscriber::fmt::time::ChronoLocal;
+
+const USAGE: &str = "用法: tax-redflush-match --bills <id,id,...> | --file <路径|-> | --import <CSV路径>";
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    tracing_subscriber::fmt()
+        .with_timer(ChronoLocal::new("%Y-%m-%d %H:%M:%S".to_string()))
+        .with_writer(std::io::stderr)
+        .with_target(true)
+        .with_level(true)
+        .init();
+
+    match run().await {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::from(2)
+        }
+    }
+}
+
+async fn run() -> Result<ExitCode, Box<dyn std::error::Error + Send + Sync>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let config = AppConfig::from_env();
+    tables::init(&config.tables)?;
+
+    if let ["--import", path] = args.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+        let pool = create_pool(&config.database.url, &config.pool).await?;
+        let rows = queries::import_csv_file(&pool, Path::new(path)).await?;
+        println!("{}", rows);
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let bill_ids = read_bill_ids(args)?;
+    let pool = create_pool(&config.database.url, &config.pool).await?;
+    let matcher = InvoiceCentricMatcher::new(pool, config.matching.clone());
+
+    let stats = matcher.batch_match(&bill_ids).await?;
+    println!("{}", serde_json::to_string_pretty(&stats)?);
+
+    let unmatched_bills = stats.iter().filter(|s| !s.unmatched.is_empty()).count();
+    if unmatched_bills > 0 {
+        tracing::warn!("{}/{} 个单据有未满足的SKU", unmatched_bills, stats.len());
+        return Ok(ExitCode::from(1));
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// 从 --bills 或 --file 参数读取单据ID，存在无法解析的ID时报错
+fn read_bill_ids(args: Vec<String>) -> Result<Vec<i64>, Box<dyn std::error::Error + Send + Sync>> {
+    let content = match args.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+        ["--bills", list] => list.replace(',', "\n"),
+        ["--file", "-"] => {
+            let mut content = String::new();
+            std::io::stdin().read_to_string(&mut content)?;
+            content
+        }
+        ["--file", path] => std::fs::read_to_string(path)?,
+        _ => return Err(USAGE.into()),
+    };
+
+    let parsed = parse_bill_ids(&content);
+    if let Some(invalid) = parsed.invalid.first() {
+        return Err(format!(
+            "{} 行无法解析为单据ID, 第 {} 行 \"{}\": {}",
+            parsed.invalid.len(), invalid.line, invalid.content, invalid.reason
+        )
+        .into());
+    }
+    if parsed.bill_ids.is_empty() {
+        return Err(format!("没有单据ID\n{}", USAGE).into());
+    }
+    if parsed.duplicates > 0 {
+        tracing::warn!("{} 个重复的单据ID, 已去重", parsed.duplicates);
+    }
+    Ok(parsed.bill_ids)
+}
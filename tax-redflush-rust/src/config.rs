@@ -1,3 +1,5 @@
+use bigdecimal::{BigDecimal, Zero};
+use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
 
 /// 应用配置
@@ -5,12 +7,28 @@ use serde::{Deserialize, Serialize};
 pub struct AppConfig {
     pub server: ServerConfig,
     pub database: DatabaseConfig,
+    pub pool: PoolConfig,
+    pub matching: MatchingConfig,
+    pub tables: TableConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServerConfig {
     pub host: String,
     pub port: u16,
+    /// /api/match/* 接口超时时间（秒），超时返回 504；None 表示不限制
+    #[serde(default)]
+    pub request_timeout_secs: Option<u64>,
+    /// 停机宽限期（秒）: 收到 SIGTERM/Ctrl-C 后不再接受新请求，最多等待该时长让进行中的匹配完成
+    #[serde(default = "default_shutdown_grace_secs")]
+    pub shutdown_grace_secs: u64,
+    /// 开启管理诊断接口 (/api/diag/*)，默认关闭
+    #[serde(default)]
+    pub admin_api: bool,
+}
+
+fn default_shutdown_grace_secs() -> u64 {
+    30
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -18,17 +36,1113 @@ pub struct DatabaseConfig {
     pub url: String,
 }
 
+/// 数据表配置: 各表名由固定基础名加环境后缀组成（如 t_sim_match_bill_1201），不同环境只需修改后缀
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TableConfig {
+    /// 表名后缀 (默认 _1201)
+    pub suffix: String,
+}
+
+/// 表名后缀最大长度（PostgreSQL 标识符上限 63 字节，需留出基础名长度）
+const MAX_TABLE_SUFFIX_LEN: usize = 32;
+
+impl Default for TableConfig {
+    fn default() -> Self {
+        Self { suffix: "_1201".to_string() }
+    }
+}
+
+impl TableConfig {
+    /// 从环境变量加载表名后缀 (TABLE_SUFFIX)，未设置时使用默认值；合法性由 validate 检查
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self { suffix: std::env::var("TABLE_SUFFIX").unwrap_or(defaults.suffix) }
+    }
+
+    /// 后缀会拼接进 SQL，只允许字母、数字和下划线，防止注入
+    pub fn validate(&self) -> Result<(), String> {
+        if self.suffix.len() > MAX_TABLE_SUFFIX_LEN {
+            return Err(format!("TABLE_SUFFIX 过长 (最多 {} 个字符): {}", MAX_TABLE_SUFFIX_LEN, self.suffix));
+        }
+        if !self.suffix.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(format!("TABLE_SUFFIX 只能包含字母、数字和下划线: {}", self.suffix));
+        }
+        Ok(())
+    }
+}
+
+/// 数据库连接池配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PoolConfig {
+    /// 最大连接数
+    pub max_connections: u32,
+    /// 常驻最小连接数 (None 表示不预留)
+    pub min_connections: Option<u32>,
+    /// 获取连接的超时时间（秒）
+    pub acquire_timeout_secs: u64,
+    /// 慢查询日志阈值（秒），超过后以 WARN 级别记录
+    pub slow_statement_secs: u64,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            max_connections: 20,
+            min_connections: None,
+            acquire_timeout_secs: 10,
+            slow_statement_secs: 5,
+        }
+    }
+}
+
+impl PoolConfig {
+    /// 从环境变量加载连接池配置，未设置或无效时使用默认值
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let max_connections = env_parse("DB_MAX_CONNECTIONS")
+            .filter(|&n: &u32| n > 0)
+            .unwrap_or(defaults.max_connections);
+        Self {
+            max_connections,
+            // 最小连接数不能超过最大连接数
+            min_connections: env_parse("DB_MIN_CONNECTIONS")
+                .filter(|&n: &u32| n > 0)
+                .map(|n| n.min(max_connections))
+                .or(defaults.min_connections),
+            acquire_timeout_secs: env_parse("DB_ACQUIRE_TIMEOUT_SECS")
+                .filter(|&n: &u64| n > 0)
+                .unwrap_or(defaults.acquire_timeout_secs),
+            slow_statement_secs: env_parse("DB_SLOW_STATEMENT_SECS")
+                .filter(|&n: &u64| n > 0)
+                .unwrap_or(defaults.slow_statement_secs),
+        }
+    }
+}
+
+/// 匹配算法配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MatchingConfig {
+    /// 单个结果文件的最大行数，超过则拆分为多个 part 文件 (None 表示不拆分)
+    pub max_rows_per_file: Option<usize>,
+    /// 结果写入配置
+    pub insert: InsertConfig,
+    /// Invoice-Centric 发票复用策略
+    pub reuse_policy: ReusePolicy,
+    /// 选票评分配置
+    pub scoring: ScoringConfig,
+    /// CSV 导出配置
+    pub csv: CsvConfig,
+    /// 候选发票取数配置
+    pub candidates: CandidateConfig,
+    /// 允许超额匹配的比例 (如 0.005 表示 +0.5%)，按SKU需求金额计算 (None 表示不允许超额)
+    pub over_match_tolerance: Option<BigDecimal>,
+    /// 金额为 0 的单据明细处理策略
+    pub zero_amount_policy: ZeroAmountPolicy,
+    /// 记录每轮选中发票的评分分解 (写入 {output_dir}/match_audit_{bill_id}.json)
+    pub audit: bool,
+    /// 同一销购方税号对同时查询数据库的单据数上限 (None 表示不限)
+    /// 服务级配置，启动时生效，不支持请求级覆盖
+    pub tax_pair_concurrency: Option<usize>,
+    /// 需求下限: SKU剩余需求低于该值时不再追匹配，记为可忽略缺口 (None 表示不启用)
+    pub requirement_floor: Option<BigDecimal>,
+    /// 单行最小匹配金额: 规整后低于该值的匹配不输出结果行，残差留作缺口 (None 表示不限制)
+    pub min_match_amount: Option<BigDecimal>,
+    /// 整SKU模式: 未能完全满足的SKU撤销已匹配部分，不输出部分匹配结果（不与流式导出同时生效）
+    pub require_full_sku: bool,
+    /// 批量结束后写入汇总清单 ({output_dir}/manifest_{batch_id}.json)
+    pub batch_manifest: bool,
+    /// 需求约束模式: 仅金额，或金额与数量同时约束
+    pub constraint_mode: ConstraintMode,
+    /// 导出被过滤排除的候选明细及原因 (写入 {output_dir}/rejected_{bill_id}.csv)
+    pub export_rejected: bool,
+    /// 历史模拟: 仅使用该日期（含）之前开具的发票 (None 表示不限)
+    /// 仅支持请求级设置
+    pub as_of: Option<NaiveDate>,
+    /// 匹配结果文件格式 (Invoice-Centric)
+    pub output_format: OutputFormat,
+    /// 匹配结果边产生边写入 CSV，不在内存中保留 (仅 CSV、不拆分、非整SKU模式、非 dry_run/include_results 时生效)
+    pub stream_results: bool,
+    /// 结果、审计、清单等文件的输出目录 (不存在时自动创建)
+    /// 服务级配置，启动时生效，不支持请求级覆盖
+    pub output_dir: String,
+    /// 瞬时数据库错误（连接超时、连接断开）的最大尝试次数，候选查询与结果写入按指数退避重试 (1 表示不重试)
+    pub db_retry_attempts: usize,
+    /// Invoice-Centric 批量匹配同时处理的单据数 (至少 1，上限为连接池的一半，候选明细查询并发在单据间平分)
+    pub bill_concurrency: usize,
+    /// 金额容差: 扣减后剩余不超过该值的SKU视为已满足，不计缺口 (None 表示剩余须扣减到 0)
+    pub amount_tolerance: Option<BigDecimal>,
+    /// Invoice-Centric 匹配维度: 按金额，或按数量（匹配结果 fmatchamount 为匹配数量）
+    pub match_by: MatchBy,
+    /// 红字模式: 单据明细金额为负时只匹配负数（红字）发票明细，结果金额保留负号
+    /// 关闭时按金额绝对值匹配正数发票 (原有行为)
+    pub sign_aware: bool,
+    /// 记录每条匹配结果的选票依据: 选中轮次、选中时评分与覆盖SKU数 (写入 {output_dir}/match_explain_{bill_id}.csv)
+    pub explain: bool,
+    /// 合并同一单据、同一发票明细、同一SKU的多条匹配结果为一行 (匹配金额求和)
+    pub merge_rows: bool,
+}
+
+impl Default for MatchingConfig {
+    fn default() -> Self {
+        Self {
+            max_rows_per_file: None,
+            insert: InsertConfig::default(),
+            reuse_policy: ReusePolicy::Reuse,
+            scoring: ScoringConfig::default(),
+            csv: CsvConfig::default(),
+            candidates: CandidateConfig::default(),
+            over_match_tolerance: None,
+            zero_amount_policy: ZeroAmountPolicy::Skip,
+            audit: false,
+            tax_pair_concurrency: None,
+            requirement_floor: None,
+            min_match_amount: None,
+            require_full_sku: false,
+            batch_manifest: false,
+            constraint_mode: ConstraintMode::AmountOnly,
+            export_rejected: false,
+            as_of: None,
+            output_format: OutputFormat::Csv,
+            stream_results: false,
+            output_dir: "logs".to_string(),
+            db_retry_attempts: 3,
+            bill_concurrency: 4,
+            amount_tolerance: None,
+            match_by: MatchBy::Amount,
+            sign_aware: false,
+            explain: false,
+            merge_rows: false,
+        }
+    }
+}
+
+/// CSV 导出配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CsvConfig {
+    /// CSV 导出中空值的表示 (默认 `\N`，供 PostgreSQL COPY 识别为 NULL)
+    pub null_token: String,
+    /// CSV 字段分隔符 (默认 `,`，仅 copy 格式生效；需为 ASCII 且不能是引号、反斜杠或换行)
+    pub delimiter: char,
+    /// CSV 导出格式
+    pub profile: CsvProfile,
+}
+
+impl Default for CsvConfig {
+    fn default() -> Self {
+        Self {
+            null_token: "\\N".to_string(),
+            delimiter: ',',
+            profile: CsvProfile::Copy,
+        }
+    }
+}
+
+impl CsvConfig {
+    /// 从环境变量加载CSV 导出配置
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            null_token: std::env::var("CSV_NULL_TOKEN").unwrap_or(defaults.null_token),
+            delimiter: env_parse("CSV_DELIMITER")
+                .filter(|&c: &char| is_valid_csv_delimiter(c))
+                .unwrap_or(defaults.delimiter),
+            profile: env_parse("CSV_PROFILE").unwrap_or(defaults.profile),
+        }
+    }
+
+    /// 合并请求级覆盖
+    pub fn with_overrides(&self, overrides: &CsvConfigOverride) -> Self {
+        Self {
+            null_token: overrides
+                .null_token
+                .clone()
+                .unwrap_or_else(|| self.null_token.clone()),
+            delimiter: overrides
+                .delimiter
+                .filter(|&c| is_valid_csv_delimiter(c))
+                .unwrap_or(self.delimiter),
+            profile: overrides.profile.unwrap_or(self.profile),
+        }
+    }
+}
+
+/// CSV 导出配置的请求级覆盖
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CsvConfigOverride {
+    pub null_token: Option<String>,
+    pub delimiter: Option<char>,
+    pub profile: Option<CsvProfile>,
+}
+
+/// 结果写入配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InsertConfig {
+    /// 数据库批量插入超时时间（秒）
+    pub timeout_secs: u64,
+    /// 插入超时后的处理策略
+    pub timeout_policy: InsertTimeoutPolicy,
+    /// SKU-Centric 批量匹配时每 N 个单据提交一次事务 (None 表示逐单据写入)
+    pub commit_every: Option<usize>,
+    /// 匹配结果写入方式: 导出文件，或通过 COPY 直接写入数据库 (超时按 timeout_policy 处理)
+    pub write_mode: ResultWriteMode,
+}
+
+impl Default for InsertConfig {
+    fn default() -> Self {
+        Self {
+            timeout_secs: 30,
+            timeout_policy: InsertTimeoutPolicy::FailBill,
+            commit_every: None,
+            write_mode: ResultWriteMode::Export,
+        }
+    }
+}
+
+impl InsertConfig {
+    /// 从环境变量加载结果写入配置
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            timeout_secs: env_parse("INSERT_TIMEOUT_SECS").unwrap_or(defaults.timeout_secs),
+            timeout_policy: env_parse("INSERT_TIMEOUT_POLICY").unwrap_or(defaults.timeout_policy),
+            commit_every: env_parse("COMMIT_EVERY")
+                .filter(|&n: &usize| n > 0)
+                .or(defaults.commit_every),
+            write_mode: env_parse("RESULT_WRITE_MODE").unwrap_or(defaults.write_mode),
+        }
+    }
+
+    /// 合并请求级覆盖
+    pub fn with_overrides(&self, overrides: &InsertConfigOverride) -> Self {
+        Self {
+            timeout_secs: overrides.timeout_secs.unwrap_or(self.timeout_secs),
+            timeout_policy: overrides.timeout_policy.unwrap_or(self.timeout_policy),
+            commit_every: overrides.commit_every.or(self.commit_every),
+            write_mode: overrides.write_mode.unwrap_or(self.write_mode),
+        }
+    }
+}
+
+/// 结果写入配置的请求级覆盖
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InsertConfigOverride {
+    pub timeout_secs: Option<u64>,
+    pub timeout_policy: Option<InsertTimeoutPolicy>,
+    pub commit_every: Option<usize>,
+    pub write_mode: Option<ResultWriteMode>,
+}
+
+/// 选票评分配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScoringConfig {
+    /// 整数化评分的金额缩放倍数（100 = 精确到分，10000 = 精确到四位小数）
+    pub score_scale: i64,
+    /// 匹配金额规整的小数位数，默认 None 即不规整（匹配金额保持明细原始精度，rounding_mode 不生效）
+    pub amount_scale: Option<i64>,
+    /// 匹配金额规整时的舍入方式（仅 amount_scale 设置时生效）
+    pub rounding_mode: RoundingMode,
+    /// 惰性堆容量上限，仅保留评分最高的 K 张发票，其余在需要时重建堆回收 (None 表示不限)
+    pub max_heap_size: Option<usize>,
+    /// Invoice-Centric 选票策略: 贪心，或求解使用发票数最少的组合
+    pub strategy: MatchStrategy,
+    /// 最少发票求解的时间预算（毫秒），超时使用已找到的最优可行解
+    pub min_invoices_budget_ms: u64,
+    /// 评分相同（且覆盖SKU数相同）的发票按开票时间先后选取: 不启用、先开先用或后开先用
+    pub date_preference: DatePreference,
+    /// 贪心选票排序: 按评分（覆盖金额），或优先剩余碎片最少的发票（尽量用完一张再开下一张）
+    pub selection_mode: SelectionMode,
+}
+
+impl Default for ScoringConfig {
+    fn default() -> Self {
+        Self {
+            score_scale: 100,
+            amount_scale: None,
+            rounding_mode: RoundingMode::RoundDown,
+            max_heap_size: None,
+            strategy: MatchStrategy::Greedy,
+            min_invoices_budget_ms: 2000,
+            date_preference: DatePreference::Off,
+            selection_mode: SelectionMode::Coverage,
+        }
+    }
+}
+
+impl ScoringConfig {
+    /// 从环境变量加载选票评分配置
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            score_scale: env_parse("SCORE_SCALE")
+                .filter(|&n: &i64| n > 0)
+                .unwrap_or(defaults.score_scale),
+            amount_scale: env_parse("AMOUNT_SCALE").or(defaults.amount_scale),
+            rounding_mode: env_parse("ROUNDING_MODE").unwrap_or(defaults.rounding_mode),
+            max_heap_size: env_parse("MAX_HEAP_SIZE")
+                .filter(|&n: &usize| n > 0)
+                .or(defaults.max_heap_size),
+            strategy: env_parse("MATCH_STRATEGY").unwrap_or(defaults.strategy),
+            min_invoices_budget_ms: env_parse("MIN_INVOICES_BUDGET_MS")
+                .filter(|&n: &u64| n > 0)
+                .unwrap_or(defaults.min_invoices_budget_ms),
+            date_preference: env_parse("INVOICE_DATE_PREFERENCE").unwrap_or(defaults.date_preference),
+            selection_mode: env_parse("INVOICE_SELECTION_MODE").unwrap_or(defaults.selection_mode),
+        }
+    }
+
+    /// 合并请求级覆盖
+    pub fn with_overrides(&self, overrides: &ScoringConfigOverride) -> Self {
+        Self {
+            score_scale: overrides
+                .score_scale
+                .filter(|&n| n > 0)
+                .unwrap_or(self.score_scale),
+            amount_scale: overrides.amount_scale.or(self.amount_scale),
+            rounding_mode: overrides.rounding_mode.unwrap_or(self.rounding_mode),
+            max_heap_size: overrides
+                .max_heap_size
+                .filter(|&n| n > 0)
+                .or(self.max_heap_size),
+            strategy: overrides.strategy.unwrap_or(self.strategy),
+            min_invoices_budget_ms: overrides
+                .min_invoices_budget_ms
+                .filter(|&n| n > 0)
+                .unwrap_or(self.min_invoices_budget_ms),
+            date_preference: overrides.date_preference.unwrap_or(self.date_preference),
+            selection_mode: overrides.selection_mode.unwrap_or(self.selection_mode),
+        }
+    }
+}
+
+/// 选票评分配置的请求级覆盖
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScoringConfigOverride {
+    pub score_scale: Option<i64>,
+    pub amount_scale: Option<i64>,
+    pub rounding_mode: Option<RoundingMode>,
+    pub max_heap_size: Option<usize>,
+    pub strategy: Option<MatchStrategy>,
+    pub min_invoices_budget_ms: Option<u64>,
+    pub date_preference: Option<DatePreference>,
+    pub selection_mode: Option<SelectionMode>,
+}
+
+/// 候选发票取数配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CandidateConfig {
+    /// 候选发票最小覆盖金额，低于该值的发票仅在回退时使用 (None 表示不过滤)
+    pub min_coverage_amount: Option<BigDecimal>,
+    /// 候选明细的发票ID不在候选发票列表中时的处理策略
+    pub mismatch_policy: MismatchPolicy,
+    /// 候选发票按覆盖度分层加载时每层的发票数 (None 表示一次加载全部)
+    pub tier_size: Option<usize>,
+    /// 候选发票两阶段取数使用的快照隔离级别 (Off 表示不开启事务)
+    pub snapshot_isolation: SnapshotIsolation,
+    /// 候选明细每批查询的发票数 (1 ~ MAX_FETCH_BATCH_SIZE，超出按上限处理)
+    pub fetch_batch_size: usize,
+    /// 候选明细并发查询的批数 (至少 1，上限为连接池的一半)
+    pub fetch_concurrency: usize,
+    /// 单价一致性: 发票明细单价偏离单据明细单价超过该比例时不参与匹配 (如 0.05 表示 ±5%，None 表示不校验)
+    pub unit_price_tolerance: Option<BigDecimal>,
+    /// 候选明细每张发票最多保留的明细数，按金额降序取前 N 条，在 SQL 中截取 (None 表示不限)
+    pub max_items_per_invoice: Option<usize>,
+}
+
+impl Default for CandidateConfig {
+    fn default() -> Self {
+        Self {
+            min_coverage_amount: None,
+            mismatch_policy: MismatchPolicy::Warn,
+            tier_size: None,
+            snapshot_isolation: SnapshotIsolation::Off,
+            fetch_batch_size: 500,
+            fetch_concurrency: 10,
+            unit_price_tolerance: None,
+            max_items_per_invoice: None,
+        }
+    }
+}
+
+impl CandidateConfig {
+    /// 候选明细每批查询的发票数（限制在 1 ~ MAX_FETCH_BATCH_SIZE）
+    pub fn effective_fetch_batch_size(&self) -> usize {
+        self.fetch_batch_size.clamp(1, MAX_FETCH_BATCH_SIZE)
+    }
+
+    /// 从环境变量加载候选取数配置
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            min_coverage_amount: env_parse("MIN_COVERAGE_AMOUNT").or(defaults.min_coverage_amount),
+            mismatch_policy: env_parse("CANDIDATE_MISMATCH_POLICY")
+                .unwrap_or(defaults.mismatch_policy),
+            tier_size: env_parse("CANDIDATE_TIER_SIZE")
+                .filter(|&n: &usize| n > 0)
+                .or(defaults.tier_size),
+            snapshot_isolation: env_parse("CANDIDATE_SNAPSHOT_ISOLATION")
+                .unwrap_or(defaults.snapshot_isolation),
+            fetch_batch_size: env_parse("FETCH_BATCH_SIZE")
+                .filter(|&n: &usize| n > 0)
+                .unwrap_or(defaults.fetch_batch_size),
+            fetch_concurrency: env_parse("FETCH_CONCURRENCY")
+                .filter(|&n: &usize| n > 0)
+                .unwrap_or(defaults.fetch_concurrency),
+            unit_price_tolerance: env_parse("UNIT_PRICE_TOLERANCE").or(defaults.unit_price_tolerance),
+            max_items_per_invoice: env_parse("MAX_ITEMS_PER_INVOICE")
+                .filter(|&n: &usize| n > 0)
+                .or(defaults.max_items_per_invoice),
+        }
+    }
+
+    /// 合并请求级覆盖
+    pub fn with_overrides(&self, overrides: &CandidateConfigOverride) -> Self {
+        Self {
+            min_coverage_amount: overrides
+                .min_coverage_amount
+                .clone()
+                .or_else(|| self.min_coverage_amount.clone()),
+            mismatch_policy: overrides
+                .mismatch_policy
+                .unwrap_or(self.mismatch_policy),
+            tier_size: overrides
+                .tier_size
+                .filter(|&n| n > 0)
+                .or(self.tier_size),
+            snapshot_isolation: overrides.snapshot_isolation.unwrap_or(self.snapshot_isolation),
+            fetch_batch_size: overrides
+                .fetch_batch_size
+                .filter(|&n| n > 0)
+                .unwrap_or(self.fetch_batch_size),
+            fetch_concurrency: overrides
+                .fetch_concurrency
+                .filter(|&n| n > 0)
+                .unwrap_or(self.fetch_concurrency),
+            unit_price_tolerance: overrides
+                .unit_price_tolerance
+                .clone()
+                .or_else(|| self.unit_price_tolerance.clone()),
+            max_items_per_invoice: overrides
+                .max_items_per_invoice
+                .filter(|&n| n > 0)
+                .or(self.max_items_per_invoice),
+        }
+    }
+}
+
+/// 候选发票取数配置的请求级覆盖
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CandidateConfigOverride {
+    pub min_coverage_amount: Option<BigDecimal>,
+    pub mismatch_policy: Option<MismatchPolicy>,
+    pub tier_size: Option<usize>,
+    pub snapshot_isolation: Option<SnapshotIsolation>,
+    pub fetch_batch_size: Option<usize>,
+    pub fetch_concurrency: Option<usize>,
+    pub unit_price_tolerance: Option<BigDecimal>,
+    pub max_items_per_invoice: Option<usize>,
+}
+
+/// 插入超时处理策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InsertTimeoutPolicy {
+    /// 超时即失败，整个单据报错（原行为）
+    #[default]
+    FailBill,
+    /// 先重试一次，仍超时则降级导出 CSV
+    RetryThenCsv,
+    /// 超时后立即降级导出 CSV
+    CsvImmediately,
+}
+
+impl std::str::FromStr for InsertTimeoutPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "fail_bill" => Ok(Self::FailBill),
+            "retry_then_csv" => Ok(Self::RetryThenCsv),
+            "csv_immediately" => Ok(Self::CsvImmediately),
+            other => Err(format!("unknown insert timeout policy: {}", other)),
+        }
+    }
+}
+
+/// Invoice-Centric 发票复用策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReusePolicy {
+    /// 允许后续迭代继续使用已选发票的剩余明细（原行为）
+    #[default]
+    Reuse,
+    /// 发票被选中一次后即移出候选，不再回访；优先选择剩余明细能一次全部消费的发票
+    ConsumeOnce,
+}
+
+impl std::str::FromStr for ReusePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "reuse" => Ok(Self::Reuse),
+            "consume_once" => Ok(Self::ConsumeOnce),
+            other => Err(format!("unknown reuse policy: {}", other)),
+        }
+    }
+}
+
+/// Invoice-Centric 匹配维度
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchBy {
+    /// 按金额匹配（原行为）
+    #[default]
+    Amount,
+    /// 按数量匹配: 需求取单据明细数量，发票明细按数量扣减，不受单价差异影响
+    Quantity,
+}
+
+impl std::str::FromStr for MatchBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "amount" => Ok(Self::Amount),
+            "quantity" => Ok(Self::Quantity),
+            other => Err(format!("unknown match dimension: {}", other)),
+        }
+    }
+}
+
+/// 评分平局时的开票时间偏好
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DatePreference {
+    /// 不按开票时间区分，平局时发票ID小的优先
+    #[default]
+    Off,
+    /// 先开具的发票优先 (FIFO)
+    Fifo,
+    /// 后开具的发票优先 (LIFO)
+    Lifo,
+}
+
+impl std::str::FromStr for DatePreference {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "off" => Ok(Self::Off),
+            "fifo" => Ok(Self::Fifo),
+            "lifo" => Ok(Self::Lifo),
+            other => Err(format!("unknown date preference: {}", other)),
+        }
+    }
+}
+
+/// 贪心选票的发票排序方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SelectionMode {
+    /// 评分（可匹配金额 + 稀缺性 + 整单红冲加分）最高的发票优先（原行为）
+    #[default]
+    Coverage,
+    /// 匹配后剩余金额最少的发票优先，剩余相同再按评分，减少残留碎片
+    SmallestRemainder,
+}
+
+impl std::str::FromStr for SelectionMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "coverage" => Ok(Self::Coverage),
+            "smallest_remainder" => Ok(Self::SmallestRemainder),
+            other => Err(format!("unknown selection mode: {}", other)),
+        }
+    }
+}
+
+/// Invoice-Centric 选票策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchStrategy {
+    /// 惰性贪心，每轮选评分最高的发票（原行为）
+    #[default]
+    Greedy,
+    /// 分支定界求解满足全部需求的最少发票组合，超出时间预算时使用已找到的最优可行解，无解时回退贪心
+    MinInvoices,
+}
+
+impl std::str::FromStr for MatchStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "greedy" => Ok(Self::Greedy),
+            "min_invoices" => Ok(Self::MinInvoices),
+            other => Err(format!("unknown match strategy: {}", other)),
+        }
+    }
+}
+
+/// 金额舍入方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoundingMode {
+    /// 向下取整 (floor)，保证匹配金额不超过需求
+    #[default]
+    RoundDown,
+    /// 四舍五入 (远离零方向)，用于展示
+    RoundHalfUp,
+}
+
+impl RoundingMode {
+    /// 按指定小数位数舍入金额
+    pub fn round(&self, value: &BigDecimal, scale: i64) -> BigDecimal {
+        // with_scale 向零截断
+        let ulp = BigDecimal::new(1.into(), scale);
+        match self {
+            Self::RoundDown => {
+                let truncated = value.with_scale(scale);
+                if truncated > *value {
+                    truncated - ulp
+                } else {
+                    truncated
+                }
+            }
+            Self::RoundHalfUp => {
+                let half = ulp / BigDecimal::from(2);
+                let shifted = if *value < BigDecimal::zero() {
+                    value - half
+                } else {
+                    value + half
+                };
+                shifted.with_scale(scale)
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for RoundingMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "round_down" => Ok(Self::RoundDown),
+            "round_half_up" => Ok(Self::RoundHalfUp),
+            other => Err(format!("unknown rounding mode: {}", other)),
+        }
+    }
+}
+
+/// CSV 导出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CsvProfile {
+    /// PostgreSQL COPY 兼容格式（默认）
+    #[default]
+    Copy,
+    /// Java 旧系统格式，用于迁移期间的输出比对
+    LegacyJava,
+}
+
+impl std::str::FromStr for CsvProfile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "copy" => Ok(Self::Copy),
+            "legacy_java" => Ok(Self::LegacyJava),
+            other => Err(format!("unknown csv profile: {}", other)),
+        }
+    }
+}
+
+/// 匹配结果文件格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    /// CSV（按 csv_profile 输出，默认）
+    #[default]
+    Csv,
+    /// JSON Lines，每行一条记录，金额以字符串保留原始精度
+    JsonLines,
+    /// Parquet，金额为定点小数列（精度与结果表一致）
+    Parquet,
+}
+
+impl OutputFormat {
+    /// 结果文件扩展名
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::JsonLines => "jsonl",
+            Self::Parquet => "parquet",
+        }
+    }
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "csv" => Ok(Self::Csv),
+            "json_lines" | "jsonl" => Ok(Self::JsonLines),
+            "parquet" => Ok(Self::Parquet),
+            other => Err(format!("unknown output format: {}", other)),
+        }
+    }
+}
+
+/// 匹配结果写入方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResultWriteMode {
+    /// 导出为文件，再由导入脚本写入数据库（默认）
+    #[default]
+    Export,
+    /// 通过 COPY FROM STDIN 直接写入 t_sim_match_result_1201，不生成结果文件
+    Copy,
+}
+
+impl std::str::FromStr for ResultWriteMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "export" => Ok(Self::Export),
+            "copy" => Ok(Self::Copy),
+            other => Err(format!("unknown result write mode: {}", other)),
+        }
+    }
+}
+
+/// 金额为 0 的单据明细处理策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ZeroAmountPolicy {
+    /// 不作为需求，不计入 total_skus
+    #[default]
+    Skip,
+    /// 保留为 0 金额需求（原行为）
+    Keep,
+    /// 存在 0 金额明细时单据报错
+    Error,
+}
+
+impl std::str::FromStr for ZeroAmountPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "skip" => Ok(Self::Skip),
+            "keep" => Ok(Self::Keep),
+            "error" => Ok(Self::Error),
+            other => Err(format!("unknown zero amount policy: {}", other)),
+        }
+    }
+}
+
+/// 数据不一致处理策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MismatchPolicy {
+    /// 不处理，保留原数据
+    Ignore,
+    /// 记录告警并丢弃不一致的数据
+    #[default]
+    Warn,
+    /// 单据报错
+    Error,
+}
+
+impl std::str::FromStr for MismatchPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "ignore" => Ok(Self::Ignore),
+            "warn" => Ok(Self::Warn),
+            "error" => Ok(Self::Error),
+            other => Err(format!("unknown mismatch policy: {}", other)),
+        }
+    }
+}
+
+/// 需求约束模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConstraintMode {
+    /// 仅按金额扣减需求
+    #[default]
+    AmountOnly,
+    /// 金额与数量同时约束: 每条明细的匹配量取两者允许的较小值，保持金额/数量比例一致
+    DualConstraint,
+}
+
+impl std::str::FromStr for ConstraintMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "amount_only" | "amount" => Ok(Self::AmountOnly),
+            "dual_constraint" | "dual" => Ok(Self::DualConstraint),
+            other => Err(format!("unknown constraint mode: {}", other)),
+        }
+    }
+}
+
+/// 候选取数事务隔离级别
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SnapshotIsolation {
+    /// 不开启事务，各查询独立读取
+    #[default]
+    Off,
+    /// REPEATABLE READ 只读事务
+    RepeatableRead,
+    /// SERIALIZABLE 只读事务
+    Serializable,
+}
+
+impl SnapshotIsolation {
+    /// 开启事务后设置隔离级别的 SQL (Off 返回 None)
+    pub fn set_transaction_sql(&self) -> Option<&'static str> {
+        match self {
+            Self::Off => None,
+            Self::RepeatableRead => Some("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ READ ONLY"),
+            Self::Serializable => Some("SET TRANSACTION ISOLATION LEVEL SERIALIZABLE READ ONLY"),
+        }
+    }
+}
+
+impl std::str::FromStr for SnapshotIsolation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().replace(' ', "_").as_str() {
+            "off" | "none" => Ok(Self::Off),
+            "repeatable_read" => Ok(Self::RepeatableRead),
+            "serializable" => Ok(Self::Serializable),
+            other => Err(format!("unknown snapshot isolation: {}", other)),
+        }
+    }
+}
+
+/// 候选明细每批查询发票数的上限
+pub const MAX_FETCH_BATCH_SIZE: usize = 5000;
+
+impl MatchingConfig {
+    /// 从环境变量加载匹配配置
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            max_rows_per_file: env_parse("MAX_ROWS_PER_FILE")
+                .filter(|&n: &usize| n > 0)
+                .or(defaults.max_rows_per_file),
+            insert: InsertConfig::from_env(),
+            reuse_policy: env_parse("REUSE_POLICY").unwrap_or(defaults.reuse_policy),
+            scoring: ScoringConfig::from_env(),
+            csv: CsvConfig::from_env(),
+            candidates: CandidateConfig::from_env(),
+            over_match_tolerance: env_parse("OVER_MATCH_TOLERANCE").or(defaults.over_match_tolerance),
+            zero_amount_policy: env_parse("ZERO_AMOUNT_POLICY").unwrap_or(defaults.zero_amount_policy),
+            audit: env_parse("MATCH_AUDIT").unwrap_or(defaults.audit),
+            tax_pair_concurrency: env_parse("TAX_PAIR_CONCURRENCY")
+                .filter(|&n: &usize| n > 0)
+                .or(defaults.tax_pair_concurrency),
+            requirement_floor: env_parse("REQUIREMENT_FLOOR").or(defaults.requirement_floor),
+            min_match_amount: env_parse("MIN_MATCH_AMOUNT").or(defaults.min_match_amount),
+            require_full_sku: env_parse("REQUIRE_FULL_SKU").unwrap_or(defaults.require_full_sku),
+            batch_manifest: env_parse("BATCH_MANIFEST").unwrap_or(defaults.batch_manifest),
+            constraint_mode: env_parse("CONSTRAINT_MODE").unwrap_or(defaults.constraint_mode),
+            export_rejected: env_parse("EXPORT_REJECTED").unwrap_or(defaults.export_rejected),
+            as_of: defaults.as_of,
+            output_format: env_parse("OUTPUT_FORMAT").unwrap_or(defaults.output_format),
+            stream_results: env_parse("STREAM_RESULTS").unwrap_or(defaults.stream_results),
+            output_dir: std::env::var("OUTPUT_DIR")
+                .ok()
+                .filter(|dir| !dir.trim().is_empty())
+                .unwrap_or(defaults.output_dir),
+            db_retry_attempts: env_parse("DB_RETRY_ATTEMPTS")
+                .filter(|&n: &usize| n > 0)
+                .unwrap_or(defaults.db_retry_attempts),
+            bill_concurrency: env_parse("BILL_CONCURRENCY")
+                .filter(|&n: &usize| n > 0)
+                .unwrap_or(defaults.bill_concurrency),
+            amount_tolerance: env_parse("AMOUNT_TOLERANCE").or(defaults.amount_tolerance),
+            match_by: env_parse("MATCH_BY").unwrap_or(defaults.match_by),
+            sign_aware: env_parse("SIGN_AWARE").unwrap_or(defaults.sign_aware),
+            explain: env_parse("MATCH_EXPLAIN").unwrap_or(defaults.explain),
+            merge_rows: env_parse("MERGE_RESULT_ROWS").unwrap_or(defaults.merge_rows),
+        }
+    }
+}
+
+/// 请求级匹配选项（所有字段均可省略，省略时与服务端默认行为一致）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MatchOptions {
+    /// 限制处理的SKU数量 (用于测试)
+    pub max_skus: Option<usize>,
+    /// 覆盖服务端匹配配置
+    pub config: MatchingConfigOverride,
+    /// 仅计算并与数据库已有结果比对，不导出 (Invoice-Centric)
+    pub diff_against_existing: bool,
+    /// 响应中返回每个单据的匹配结果行 (Invoice-Centric，仍会导出 CSV)
+    pub include_results: bool,
+    /// 只计算统计，不导出 CSV 也不写任何结果文件 (Invoice-Centric，用于调参实验)
+    pub dry_run: bool,
+    /// 重新匹配: 写入前在同一事务中删除单据已有的匹配结果，避免重复 (默认 true)
+    /// 仅对直接写库生效 (SKU-Centric，Invoice-Centric write_mode=copy)
+    pub rerun: bool,
+    /// 单据在结果表中已有完整匹配结果时跳过匹配，直接返回已有结果的统计 (Invoice-Centric)
+    /// 已有结果只覆盖部分需求时仍重新匹配
+    pub skip_if_matched: bool,
+    /// 匹配完成后把每个单据的统计写入 t_sim_match_stats_1201，供看板查看历史 (默认 false，临时运行不写入)
+    /// 已取消的单据不写入；写入失败只记告警，不影响匹配结果
+    pub persist_stats: bool,
+}
+
+impl Default for MatchOptions {
+    fn default() -> Self {
+        Self {
+            max_skus: None,
+            config: MatchingConfigOverride::default(),
+            diff_against_existing: false,
+            include_results: false,
+            dry_run: false,
+            rerun: true,
+            skip_if_matched: false,
+            persist_stats: false,
+        }
+    }
+}
+
+impl MatchOptions {
+    /// 合并到服务端配置，得到本次匹配实际生效的配置
+    pub fn effective_config(&self, base: &MatchingConfig) -> MatchingConfig {
+        base.with_overrides(&self.config)
+    }
+}
+
+/// 请求级配置覆盖（未设置的字段沿用服务端配置）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MatchingConfigOverride {
+    pub max_rows_per_file: Option<usize>,
+    pub insert: InsertConfigOverride,
+    pub reuse_policy: Option<ReusePolicy>,
+    pub scoring: ScoringConfigOverride,
+    pub csv: CsvConfigOverride,
+    pub candidates: CandidateConfigOverride,
+    pub over_match_tolerance: Option<BigDecimal>,
+    pub zero_amount_policy: Option<ZeroAmountPolicy>,
+    pub audit: Option<bool>,
+    pub requirement_floor: Option<BigDecimal>,
+    pub min_match_amount: Option<BigDecimal>,
+    pub require_full_sku: Option<bool>,
+    pub batch_manifest: Option<bool>,
+    pub constraint_mode: Option<ConstraintMode>,
+    pub export_rejected: Option<bool>,
+    pub as_of: Option<NaiveDate>,
+    pub output_format: Option<OutputFormat>,
+    pub stream_results: Option<bool>,
+    pub db_retry_attempts: Option<usize>,
+    pub bill_concurrency: Option<usize>,
+    pub amount_tolerance: Option<BigDecimal>,
+    pub match_by: Option<MatchBy>,
+    pub sign_aware: Option<bool>,
+    pub explain: Option<bool>,
+    pub merge_rows: Option<bool>,
+}
+
+impl MatchingConfig {
+    /// 合并请求级覆盖，得到本次匹配实际生效的配置
+    pub fn with_overrides(&self, overrides: &MatchingConfigOverride) -> Self {
+        Self {
+            max_rows_per_file: overrides.max_rows_per_file.or(self.max_rows_per_file),
+            insert: self.insert.with_overrides(&overrides.insert),
+            reuse_policy: overrides.reuse_policy.unwrap_or(self.reuse_policy),
+            scoring: self.scoring.with_overrides(&overrides.scoring),
+            csv: self.csv.with_overrides(&overrides.csv),
+            candidates: self.candidates.with_overrides(&overrides.candidates),
+            over_match_tolerance: overrides
+                .over_match_tolerance
+                .clone()
+                .or_else(|| self.over_match_tolerance.clone()),
+            zero_amount_policy: overrides.zero_amount_policy.unwrap_or(self.zero_amount_policy),
+            audit: overrides.audit.unwrap_or(self.audit),
+            tax_pair_concurrency: self.tax_pair_concurrency,
+            requirement_floor: overrides
+                .requirement_floor
+                .clone()
+                .or_else(|| self.requirement_floor.clone()),
+            min_match_amount: overrides
+                .min_match_amount
+                .clone()
+                .or_else(|| self.min_match_amount.clone()),
+            require_full_sku: overrides.require_full_sku.unwrap_or(self.require_full_sku),
+            batch_manifest: overrides.batch_manifest.unwrap_or(self.batch_manifest),
+            constraint_mode: overrides.constraint_mode.unwrap_or(self.constraint_mode),
+            export_rejected: overrides.export_rejected.unwrap_or(self.export_rejected),
+            as_of: overrides.as_of.or(self.as_of),
+            output_format: overrides.output_format.unwrap_or(self.output_format),
+            stream_results: overrides.stream_results.unwrap_or(self.stream_results),
+            output_dir: self.output_dir.clone(),
+            db_retry_attempts: overrides
+                .db_retry_attempts
+                .filter(|&n| n > 0)
+                .unwrap_or(self.db_retry_attempts),
+            bill_concurrency: overrides
+                .bill_concurrency
+                .filter(|&n| n > 0)
+                .unwrap_or(self.bill_concurrency),
+            amount_tolerance: overrides
+                .amount_tolerance
+                .clone()
+                .or_else(|| self.amount_tolerance.clone()),
+            match_by: overrides.match_by.unwrap_or(self.match_by),
+            sign_aware: overrides.sign_aware.unwrap_or(self.sign_aware),
+            explain: overrides.explain.unwrap_or(self.explain),
+            merge_rows: overrides.merge_rows.unwrap_or(self.merge_rows),
+        }
+    }
+}
+
+/// CSV 分隔符须为单字节 ASCII，且不能与引号、转义符、换行冲突（导入脚本将其写入 COPY 语句）
+fn is_valid_csv_delimiter(c: char) -> bool {
+    c.is_ascii() && !matches!(c, '"' | '\'' | '\\' | '\n' | '\r')
+}
+
+/// 读取并解析环境变量，缺失或解析失败时返回 None
+fn env_parse<T: std::str::FromStr>(key: &str) -> Option<T> {
+    std::env::var(key).ok().and_then(|v| v.parse().ok())
+}
+
 impl Default for AppConfig {
     fn default() -> Self {
         Self {
             server: ServerConfig {
                 host: "127.0.0.1".to_string(),
                 port: 8089,
+                request_timeout_secs: None,
+                shutdown_grace_secs: default_shutdown_grace_secs(),
+                admin_api: false,
             },
             database: DatabaseConfig {
                 url: std::env::var("DATABASE_URL")
                     .unwrap_or_else(|_| "postgres://localhost/tax_redflush".to_string()),
             },
+            pool: PoolConfig::default(),
+            matching: MatchingConfig::default(),
+            tables: TableConfig::default(),
         }
     }
 }
@@ -43,11 +1157,150 @@ impl AppConfig {
                     .ok()
                     .and_then(|p| p.parse().ok())
                     .unwrap_or(8089),
+                request_timeout_secs: std::env::var("REQUEST_TIMEOUT_SECS")
+                    .ok()
+                    .and_then(|s| s.parse().ok())
+                    .filter(|&n: &u64| n > 0),
+                shutdown_grace_secs: env_parse("SHUTDOWN_GRACE_SECS").unwrap_or_else(default_shutdown_grace_secs),
+                admin_api: env_parse("ENABLE_ADMIN_API").unwrap_or(false),
             },
             database: DatabaseConfig {
                 url: std::env::var("DATABASE_URL")
                     .unwrap_or_else(|_| "postgres://localhost/tax_redflush".to_string()),
             },
+            pool: PoolConfig::from_env(),
+            matching: MatchingConfig::from_env(),
+            tables: TableConfig::from_env(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scoring_defaults_leave_match_amounts_unrounded() {
+        let scoring = MatchingConfig::default().scoring;
+
+        assert_eq!(scoring.amount_scale, None);
+        assert_eq!(scoring.rounding_mode, RoundingMode::RoundDown);
+        assert_eq!(scoring.score_scale, 100);
+    }
+
+    #[test]
+    fn match_options_defaults_when_omitted() {
+        let options: MatchOptions = serde_json::from_str("{}").unwrap();
+
+        assert!(!options.diff_against_existing);
+        assert!(options.max_skus.is_none());
+
+        let base = MatchingConfig::default();
+        let effective = options.effective_config(&base);
+        assert_eq!(effective.scoring.score_scale, base.scoring.score_scale);
+        assert_eq!(effective.insert.timeout_secs, base.insert.timeout_secs);
+        assert_eq!(effective.csv.null_token, base.csv.null_token);
+    }
+
+    #[test]
+    fn nested_overrides_replace_only_given_fields() {
+        let options: MatchOptions = serde_json::from_value(serde_json::json!({
+            "diff_against_existing": true,
+            "config": {
+                "scoring": { "score_scale": 10000 },
+                "csv": { "profile": "legacy_java" },
+                "candidates": { "tier_size": 2 },
+                "audit": true
+            }
+        }))
+        .unwrap();
+
+        let base = MatchingConfig::default();
+        let effective = options.effective_config(&base);
+
+        assert!(options.diff_against_existing);
+        assert_eq!(effective.scoring.score_scale, 10000);
+        assert_eq!(effective.scoring.rounding_mode, base.scoring.rounding_mode);
+        assert_eq!(effective.csv.profile, CsvProfile::LegacyJava);
+        assert_eq!(effective.csv.null_token, base.csv.null_token);
+        assert_eq!(effective.candidates.tier_size, Some(2));
+        assert_eq!(effective.candidates.mismatch_policy, base.candidates.mismatch_policy);
+        assert!(effective.audit);
+    }
+
+    #[test]
+    fn invalid_overrides_fall_back_to_server_config() {
+        let overrides = MatchingConfigOverride {
+            scoring: ScoringConfigOverride { score_scale: Some(0), ..Default::default() },
+            csv: CsvConfigOverride { delimiter: Some('"'), ..Default::default() },
+            ..Default::default()
+        };
+
+        let effective = MatchingConfig::default().with_overrides(&overrides);
+
+        assert_eq!(effective.scoring.score_scale, 100);
+        assert_eq!(effective.csv.delimiter, ',');
+    }
+
+    #[test]
+    fn server_only_settings_ignore_overrides() {
+        let base = MatchingConfig { tax_pair_concurrency: Some(2), ..MatchingConfig::default() };
+
+        let effective = base.with_overrides(&MatchingConfigOverride::default());
+
+        assert_eq!(effective.tax_pair_concurrency, Some(2));
+    }
+
+    #[test]
+    fn echoed_config_reflects_request_override_over_server_default() {
+        let server = MatchingConfig {
+            scoring: ScoringConfig { score_scale: 10000, ..ScoringConfig::default() },
+            reuse_policy: ReusePolicy::ConsumeOnce,
+            ..MatchingConfig::default()
+        };
+        let options: MatchOptions = serde_json::from_value(serde_json::json!({
+            "config": { "scoring": { "score_scale": 100, "rounding_mode": "round_half_up" } }
+        }))
+        .unwrap();
+
+        let echoed = serde_json::to_value(options.effective_config(&server)).unwrap();
+
+        // 请求覆盖优先于服务端配置，未覆盖的字段沿用服务端配置
+        assert_eq!(echoed["scoring"]["score_scale"], 100);
+        assert_eq!(echoed["scoring"]["rounding_mode"], "round_half_up");
+        assert_eq!(echoed["reuse_policy"], "consume_once");
+        assert_eq!(echoed["insert"]["timeout_secs"], 30);
+    }
+
+    fn decimal(value: &str) -> BigDecimal {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn round_down_never_exceeds_value() {
+        let mode = RoundingMode::RoundDown;
+
+        assert_eq!(mode.round(&decimal("0.005"), 2), decimal("0.00"));
+        assert_eq!(mode.round(&decimal("0.009"), 2), decimal("0.00"));
+        assert_eq!(mode.round(&decimal("1.015"), 2), decimal("1.01"));
+        assert_eq!(mode.round(&decimal("-0.005"), 2), decimal("-0.01"));
+        assert_eq!(mode.round(&decimal("0.01"), 2), decimal("0.01"));
+    }
+
+    #[test]
+    fn round_half_up_rounds_boundary_away_from_zero() {
+        let mode = RoundingMode::RoundHalfUp;
+
+        assert_eq!(mode.round(&decimal("0.005"), 2), decimal("0.01"));
+        assert_eq!(mode.round(&decimal("0.0049"), 2), decimal("0.00"));
+        assert_eq!(mode.round(&decimal("1.015"), 2), decimal("1.02"));
+        assert_eq!(mode.round(&decimal("-0.005"), 2), decimal("-0.01"));
+        assert_eq!(mode.round(&decimal("0.01"), 2), decimal("0.01"));
+    }
+
+    #[test]
+    fn rounding_mode_defaults_to_round_down() {
+        assert_eq!(RoundingMode::default(), RoundingMode::RoundDown);
+        assert_eq!(MatchingConfig::default().scoring.rounding_mode, RoundingMode::RoundDown);
+    }
+}
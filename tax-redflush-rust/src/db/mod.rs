@@ -1,6 +1,8 @@
 pub mod pool;
 pub mod queries;
 pub mod queries_invoice_centric;
+pub mod retry;
+pub mod tables;
 
 pub use pool::create_pool;
 pub use queries::*;
@@ -1,21 +1,23 @@
+use crate::config::PoolConfig;
 use sqlx::postgres::{PgPoolOptions, PgConnectOptions};
 use sqlx::{PgPool, ConnectOptions};
 use std::time::Duration;
 use std::str::FromStr;
 
 /// 创建数据库连接池
-pub async fn create_pool(database_url: &str) -> Result<PgPool, sqlx::Error> {
+pub async fn create_pool(database_url: &str, config: &PoolConfig) -> Result<PgPool, sqlx::Error> {
     let mut connect_options = PgConnectOptions::from_str(database_url)?;
     
-    // 设置慢查询日志阈值为 5秒
+    // 设置慢查询日志阈值
     connect_options = connect_options.log_slow_statements(
         tracing::log::LevelFilter::Warn, 
-        Duration::from_secs(5)
+        Duration::from_secs(config.slow_statement_secs)
     );
 
     PgPoolOptions::new()
-        .max_connections(20)
-        .acquire_timeout(Duration::from_secs(10))
+        .max_connections(config.max_connections)
+        .min_connections(config.min_connections.unwrap_or(0))
+        .acquire_timeout(Duration::from_secs(config.acquire_timeout_secs))
         .connect_with(connect_options)
         .await
 }
@@ -1,15 +1,29 @@
+use crate::db::tables;
 use crate::models::{InvoiceCoverage, InvoiceItemDetail};
-use sqlx::PgPool;
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use sqlx::{PgExecutor, PgPool};
+
+/// 历史模拟截止时间: as_of 次日零点（开票时间早于该时间的发票视为已开具）
+fn issued_before(as_of: Option<NaiveDate>) -> Option<NaiveDateTime> {
+    as_of.and_then(|date| date.succ_opt()).map(|date| date.and_time(NaiveTime::MIN))
+}
 
 /// 批量查询发票覆盖度统计
-/// 按SKU覆盖数量降序、总金额降序排序
+/// 按SKU覆盖数量降序、总金额（绝对值）降序排序；as_of 不为空时仅统计当日及之前开具的发票
+/// sign 为 1 时只统计正数发票，为 -1 时只统计负数（红字）发票
+/// SKU列表为空时直接返回空，不查询数据库
 pub async fn query_invoices_with_coverage(
     pool: &PgPool,
     buyer_tax_no: &str,
     seller_tax_no: &str,
     sku_list: &[String],
+    as_of: Option<NaiveDate>,
+    sign: i32,
 ) -> Result<Vec<InvoiceCoverage>, sqlx::Error> {
-    sqlx::query_as::<_, InvoiceCoverage>(
+    if sku_list.is_empty() {
+        return Ok(Vec::new());
+    }
+    let sql = format!(
         r#"
         WITH required_skus AS (
             SELECT unnest($1::varchar[]) as fspbm
@@ -18,24 +32,30 @@ pub async fn query_invoices_with_coverage(
             SELECT
                 vi.fid as invoice_id,
                 COUNT(DISTINCT vii.fspbm) as sku_coverage_count,
-                COALESCE(SUM(vii.famount), 0) as total_coverage_amount
-            FROM t_sim_vatinvoice_item_1201 vii
-            INNER JOIN t_sim_vatinvoice_1201 vi ON vi.fid = vii.fid
+                COALESCE(SUM(vii.famount * $5::int), 0) as total_coverage_amount
+            FROM {invoice_item} vii
+            INNER JOIN {invoice} vi ON vi.fid = vii.fid
             INNER JOIN required_skus rs ON vii.fspbm = rs.fspbm
             WHERE vi.fbuyertaxno = $2
               AND vi.fsalertaxno = $3
-              AND vi.ftotalamount > 0
-              AND vii.famount > 0
+              AND vi.ftotalamount * $5::int > 0
+              AND vii.famount * $5::int > 0
+              AND ($4::timestamp IS NULL OR vi.fissuetime < $4)
             GROUP BY vi.fid
         )
         SELECT invoice_id, sku_coverage_count, total_coverage_amount
         FROM invoice_coverage
-        ORDER BY sku_coverage_count DESC, total_coverage_amount DESC
+        ORDER BY sku_coverage_count DESC, total_coverage_amount DESC, invoice_id
         "#,
-    )
+        invoice = tables::invoice(),
+        invoice_item = tables::invoice_item(),
+    );
+    sqlx::query_as::<_, InvoiceCoverage>(&sql)
     .bind(sku_list)
     .bind(buyer_tax_no)
     .bind(seller_tax_no)
+    .bind(issued_before(as_of))
+    .bind(sign)
     .fetch_all(pool)
     .await
 }
@@ -46,7 +66,7 @@ pub async fn query_items_for_invoices(
     invoice_ids: &[i64],
     sku_list: &[String],
 ) -> Result<Vec<InvoiceItemDetail>, sqlx::Error> {
-    sqlx::query_as::<_, InvoiceItemDetail>(
+    let sql = format!(
         r#"
         SELECT
             vii.fid as invoice_id,
@@ -54,14 +74,20 @@ pub async fn query_items_for_invoices(
             vii.fspbm as product_code,
             vii.fnum as quantity,
             vii.famount as amount,
-            vii.funitprice as unit_price
-        FROM t_sim_vatinvoice_item_1201 vii
+            vii.funitprice as unit_price,
+            vi.fissuetime as issue_time,
+            vi.ftotalamount as invoice_total
+        FROM {invoice_item} vii
+        INNER JOIN {invoice} vi ON vi.fid = vii.fid
         WHERE vii.fid = ANY($1)
           AND vii.fspbm = ANY($2)
           AND vii.famount > 0
-        ORDER BY vii.fid, vii.famount DESC
+        ORDER BY vii.fid, vii.famount DESC, vii.fentryid
         "#,
-    )
+        invoice = tables::invoice(),
+        invoice_item = tables::invoice_item(),
+    );
+    sqlx::query_as::<_, InvoiceItemDetail>(&sql)
     .bind(invoice_ids)
     .bind(sku_list)
     .fetch_all(pool)
@@ -76,7 +102,7 @@ pub async fn query_all_candidate_items(
     seller_tax_no: &str,
     sku_list: &[String],
 ) -> Result<Vec<InvoiceItemDetail>, sqlx::Error> {
-    sqlx::query_as::<_, InvoiceItemDetail>(
+    let sql = format!(
         r#"
         SELECT
             vii.fid as invoice_id,
@@ -84,17 +110,22 @@ pub async fn query_all_candidate_items(
             vii.fspbm as product_code,
             vii.fnum as quantity,
             vii.famount as amount,
-            vii.funitprice as unit_price
-        FROM t_sim_vatinvoice_item_1201 vii
-        INNER JOIN t_sim_vatinvoice_1201 vi ON vi.fid = vii.fid
+            vii.funitprice as unit_price,
+            vi.fissuetime as issue_time,
+            vi.ftotalamount as invoice_total
+        FROM {invoice_item} vii
+        INNER JOIN {invoice} vi ON vi.fid = vii.fid
         WHERE vii.fspbm = ANY($1)
           AND vi.fbuyertaxno = $2
           AND vi.fsalertaxno = $3
           AND vi.ftotalamount > 0
           AND vii.famount > 0
-        ORDER BY vii.fid, vii.famount DESC
+        ORDER BY vii.fid, vii.famount DESC, vii.fentryid
         "#,
-    )
+        invoice = tables::invoice(),
+        invoice_item = tables::invoice_item(),
+    );
+    sqlx::query_as::<_, InvoiceItemDetail>(&sql)
     .bind(sku_list)
     .bind(buyer_tax_no)
     .bind(seller_tax_no)
@@ -103,33 +134,90 @@ pub async fn query_all_candidate_items(
 }
 
 /// Phase 1: 仅查询候选发票ID (快速筛选)
-pub async fn query_candidate_invoice_ids(
-    pool: &PgPool,
+/// 可传入连接池或事务连接 (快照取数时两阶段共用同一事务)
+/// as_of 不为空时仅返回当日及之前开具的发票；sign 为 1 时返回正数发票，为 -1 时返回负数（红字）发票
+pub async fn query_candidate_invoice_ids<'e>(
+    executor: impl PgExecutor<'e>,
     buyer_tax_no: &str,
     seller_tax_no: &str,
+    as_of: Option<NaiveDate>,
+    sign: i32,
 ) -> Result<Vec<i64>, sqlx::Error> {
-    sqlx::query_scalar::<_, i64>(
+    sqlx::query_scalar::<_, i64>(&candidate_invoice_ids_sql())
+    .bind(buyer_tax_no)
+    .bind(seller_tax_no)
+    .bind(issued_before(as_of))
+    .bind(sign)
+    .fetch_all(executor)
+    .await
+}
+
+/// 仅统计候选发票数（条件与 query_candidate_invoice_ids 相同），用于匹配前预估规模
+pub async fn count_candidate_invoices<'e>(
+    executor: impl PgExecutor<'e>,
+    buyer_tax_no: &str,
+    seller_tax_no: &str,
+    as_of: Option<NaiveDate>,
+    sign: i32,
+) -> Result<i64, sqlx::Error> {
+    let sql = format!("SELECT count(*) FROM ({}) candidates", candidate_invoice_ids_sql());
+    sqlx::query_scalar::<_, i64>(&sql)
+    .bind(buyer_tax_no)
+    .bind(seller_tax_no)
+    .bind(issued_before(as_of))
+    .bind(sign)
+    .fetch_one(executor)
+    .await
+}
+
+fn candidate_invoice_ids_sql() -> String {
+    format!(
         r#"
         SELECT fid
-        FROM t_sim_vatinvoice_1201
+        FROM {invoice}
         WHERE fbuyertaxno = $1
           AND fsalertaxno = $2
-          AND ftotalamount > 0
+          AND ftotalamount * $4::int > 0
+          AND ($3::timestamp IS NULL OR fissuetime < $3)
+        ORDER BY fid
         "#,
+        invoice = tables::invoice(),
     )
-    .bind(buyer_tax_no)
-    .bind(seller_tax_no)
-    .fetch_all(pool)
-    .await
 }
 
 /// Phase 2: 按发票ID列表批量查询明细
-pub async fn query_items_by_fids_and_skus(
-    pool: &PgPool,
+/// sign 为 1 时只返回正数明细，为 -1 时只返回负数（红字）明细，按金额绝对值降序
+/// item_cap 为 Some(N) 时每张发票只保留金额最大的前 N 条明细（按同一排序用窗口函数截取）
+/// 发票ID或SKU列表为空时直接返回空，不查询数据库
+pub async fn query_items_by_fids_and_skus<'e>(
+    executor: impl PgExecutor<'e>,
     invoice_ids: &[i64],
     sku_list: &[String],
+    sign: i32,
+    item_cap: Option<usize>,
 ) -> Result<Vec<InvoiceItemDetail>, sqlx::Error> {
-    sqlx::query_as::<_, InvoiceItemDetail>(
+    if invoice_ids.is_empty() || sku_list.is_empty() {
+        return Ok(Vec::new());
+    }
+    if let Some(cap) = item_cap {
+        return sqlx::query_as::<_, InvoiceItemDetail>(&top_items_by_fids_and_skus_sql())
+            .bind(invoice_ids)
+            .bind(sku_list)
+            .bind(sign)
+            .bind(i64::try_from(cap).unwrap_or(i64::MAX))
+            .fetch_all(executor)
+            .await;
+    }
+    sqlx::query_as::<_, InvoiceItemDetail>(&items_by_fids_and_skus_sql())
+    .bind(invoice_ids)
+    .bind(sku_list)
+    .bind(sign)
+    .fetch_all(executor)
+    .await
+}
+
+fn items_by_fids_and_skus_sql() -> String {
+    format!(
         r#"
         SELECT
             vii.fid as invoice_id,
@@ -137,16 +225,92 @@ pub async fn query_items_by_fids_and_skus(
             vii.fspbm as product_code,
             vii.fnum as quantity,
             vii.famount as amount,
-            vii.funitprice as unit_price
-        FROM t_sim_vatinvoice_item_1201 vii
+            vii.funitprice as unit_price,
+            vi.fissuetime as issue_time,
+            vi.ftotalamount as invoice_total
+        FROM {invoice_item} vii
+        INNER JOIN {invoice} vi ON vi.fid = vii.fid
         WHERE vii.fid = ANY($1)
           AND vii.fspbm = ANY($2)
-          AND vii.famount > 0
-        ORDER BY vii.fid, vii.famount DESC
+          AND vii.famount * $3::int > 0
+        ORDER BY vii.fid, vii.famount * $3::int DESC, vii.fentryid
         "#,
+        invoice = tables::invoice(),
+        invoice_item = tables::invoice_item(),
     )
-    .bind(invoice_ids)
-    .bind(sku_list)
-    .fetch_all(pool)
+}
+
+/// 每张发票只取金额最大的前 $4 条明细，排序与不截取时一致
+fn top_items_by_fids_and_skus_sql() -> String {
+    format!(
+        r#"
+        SELECT invoice_id, item_id, product_code, quantity, amount, unit_price, issue_time, invoice_total
+        FROM (
+            SELECT
+                vii.fid as invoice_id,
+                vii.fentryid as item_id,
+                vii.fspbm as product_code,
+                vii.fnum as quantity,
+                vii.famount as amount,
+                vii.funitprice as unit_price,
+                vi.fissuetime as issue_time,
+                vi.ftotalamount as invoice_total,
+                ROW_NUMBER() OVER (
+                    PARTITION BY vii.fid
+                    ORDER BY vii.famount * $3::int DESC, vii.fentryid
+                ) as item_rank
+            FROM {invoice_item} vii
+            INNER JOIN {invoice} vi ON vi.fid = vii.fid
+            WHERE vii.fid = ANY($1)
+              AND vii.fspbm = ANY($2)
+              AND vii.famount * $3::int > 0
+        ) ranked
+        WHERE item_rank <= $4
+        ORDER BY invoice_id, amount * $3::int DESC, item_id
+        "#,
+        invoice = tables::invoice(),
+        invoice_item = tables::invoice_item(),
+    )
+}
+
+/// 诊断: 对 Phase 1 候选发票ID查询执行 EXPLAIN (ANALYZE, FORMAT JSON)，返回执行计划
+/// ANALYZE 会实际执行查询（只读）
+pub async fn explain_candidate_invoice_ids(
+    pool: &PgPool,
+    buyer_tax_no: &str,
+    seller_tax_no: &str,
+    as_of: Option<NaiveDate>,
+    sign: i32,
+) -> Result<serde_json::Value, sqlx::Error> {
+    let sql = format!("EXPLAIN (ANALYZE, FORMAT JSON) {}", candidate_invoice_ids_sql());
+    sqlx::query_scalar::<_, serde_json::Value>(&sql)
+    .bind(buyer_tax_no)
+    .bind(seller_tax_no)
+    .bind(issued_before(as_of))
+    .bind(sign)
+    .fetch_one(pool)
     .await
 }
+
+/// 诊断: 对 Phase 2 候选明细查询（含每张发票明细数截取）执行 EXPLAIN (ANALYZE, FORMAT JSON)，返回执行计划
+pub async fn explain_items_by_fids_and_skus(
+    pool: &PgPool,
+    invoice_ids: &[i64],
+    sku_list: &[String],
+    sign: i32,
+    item_cap: Option<usize>,
+) -> Result<serde_json::Value, sqlx::Error> {
+    let query = match item_cap {
+        Some(_) => top_items_by_fids_and_skus_sql(),
+        None => items_by_fids_and_skus_sql(),
+    };
+    let sql = format!("EXPLAIN (ANALYZE, FORMAT JSON) {}", query);
+    let mut explain = sqlx::query_scalar::<_, serde_json::Value>(&sql)
+        .bind(invoice_ids)
+        .bind(sku_list)
+        .bind(sign);
+    if let Some(cap) = item_cap {
+        explain = explain.bind(i64::try_from(cap).unwrap_or(i64::MAX));
+    }
+    explain.fetch_one(pool).await
+}
@@ -0,0 +1,118 @@
+use std::future::Future;
+use std::time::Duration;
+
+/// 首次重试前的等待时间，之后每次翻倍
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(200);
+
+/// 是否为连接层面的错误: IO 错误（连接被代理断开等）或 SQLSTATE 08 类连接异常、57P01~57P03 服务端关闭
+pub fn is_connection_error(err: &sqlx::Error) -> bool {
+    match err {
+        sqlx::Error::Io(_) => true,
+        sqlx::Error::Database(db) => db
+            .code()
+            .is_some_and(|code| code.starts_with("08") || matches!(&*code, "57P01" | "57P02" | "57P03")),
+        _ => false,
+    }
+}
+
+/// 是否为可重试的瞬时错误: 获取连接超时或连接错误；约束冲突、SQL 错误等逻辑错误不重试
+pub fn is_retryable(err: &sqlx::Error) -> bool {
+    matches!(err, sqlx::Error::PoolTimedOut) || is_connection_error(err)
+}
+
+/// 执行数据库操作，遇到可重试错误时按指数退避重试，最多执行 `attempts` 次（至少 1 次）
+pub async fn with_retry<T, F, Fut>(label: &str, attempts: usize, operation: F) -> Result<T, sqlx::Error>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, sqlx::Error>>,
+{
+    with_retry_when(label, attempts, is_retryable, operation).await
+}
+
+/// 按自定义条件重试，用于插入等自带超时处理、只应在连接错误时重试的操作
+pub async fn with_retry_when<T, E, F, Fut, P>(
+    label: &str,
+    attempts: usize,
+    should_retry: P,
+    mut operation: F,
+) -> Result<T, E>
+where
+    E: std::fmt::Display,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    P: Fn(&E) -> bool,
+{
+    let attempts = attempts.max(1);
+    let mut delay = RETRY_BASE_DELAY;
+    let mut attempt = 1;
+    loop {
+        match operation().await {
+            Err(e) if attempt < attempts && should_retry(&e) => {
+                tracing::warn!("{} 失败 (第 {}/{} 次), {:?} 后重试: {}", label, attempt, attempts, delay, e);
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    fn connection_reset() -> sqlx::Error {
+        sqlx::Error::Io(std::io::Error::new(std::io::ErrorKind::ConnectionReset, "proxy dropped connection"))
+    }
+
+    #[tokio::test]
+    async fn transient_failures_are_retried_until_success() {
+        let calls = Cell::new(0);
+
+        let result = with_retry("测试查询", 3, || {
+            calls.set(calls.get() + 1);
+            let call = calls.get();
+            async move {
+                match call {
+                    1 => Err(connection_reset()),
+                    2 => Err(sqlx::Error::PoolTimedOut),
+                    _ => Ok(call),
+                }
+            }
+        })
+        .await;
+
+        assert_eq!(result.unwrap(), 3);
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[tokio::test]
+    async fn logic_errors_are_not_retried() {
+        let calls = Cell::new(0);
+
+        let result: Result<(), _> = with_retry("测试查询", 3, || {
+            calls.set(calls.get() + 1);
+            async { Err(sqlx::Error::RowNotFound) }
+        })
+        .await;
+
+        assert!(matches!(result, Err(sqlx::Error::RowNotFound)));
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_configured_attempts() {
+        let calls = Cell::new(0);
+
+        let result: Result<(), _> = with_retry("测试查询", 2, || {
+            calls.set(calls.get() + 1);
+            async { Err(connection_reset()) }
+        })
+        .await;
+
+        assert!(matches!(result, Err(sqlx::Error::Io(_))));
+        assert_eq!(calls.get(), 2);
+    }
+}
@@ -0,0 +1,111 @@
+use crate::config::TableConfig;
+use std::sync::OnceLock;
+
+/// 各表实际名称，由基础名加环境后缀组成（如 t_sim_match_bill + _1201）
+#[derive(Debug, Clone)]
+struct TableNames {
+    bill: String,
+    bill_item: String,
+    invoice: String,
+    invoice_item: String,
+    result: String,
+    stats: String,
+}
+
+impl TableNames {
+    fn with_suffix(suffix: &str) -> Self {
+        Self {
+            bill: format!("t_sim_match_bill{}", suffix),
+            bill_item: format!("t_sim_match_bill_item{}", suffix),
+            invoice: format!("t_sim_vatinvoice{}", suffix),
+            invoice_item: format!("t_sim_vatinvoice_item{}", suffix),
+            result: format!("t_sim_match_result{}", suffix),
+            stats: format!("t_sim_match_stats{}", suffix),
+        }
+    }
+}
+
+static TABLES: OnceLock<TableNames> = OnceLock::new();
+
+/// 启动时按配置设置表名（只能设置一次），后缀不合法时返回错误
+/// 未调用时使用默认后缀
+pub fn init(config: &TableConfig) -> Result<(), String> {
+    config.validate()?;
+    let names = TableNames::with_suffix(&config.suffix);
+    TABLES
+        .set(names)
+        .map_err(|_| "表名已初始化, 不能重复设置".to_string())?;
+    tracing::info!("数据表后缀: {}", config.suffix);
+    Ok(())
+}
+
+fn names() -> &'static TableNames {
+    TABLES.get_or_init(|| TableNames::with_suffix(&TableConfig::default().suffix))
+}
+
+/// 单据表
+pub fn bill() -> &'static str {
+    &names().bill
+}
+
+/// 单据明细表
+pub fn bill_item() -> &'static str {
+    &names().bill_item
+}
+
+/// 发票表
+pub fn invoice() -> &'static str {
+    &names().invoice
+}
+
+/// 发票明细表
+pub fn invoice_item() -> &'static str {
+    &names().invoice_item
+}
+
+/// 匹配结果表
+pub fn result() -> &'static str {
+    &names().result
+}
+
+/// 匹配统计表
+pub fn stats() -> &'static str {
+    &names().stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn valid_suffix_is_appended_to_every_table() {
+        let config = TableConfig { suffix: "_2024_q1".to_string() };
+        assert!(config.validate().is_ok());
+
+        let names = TableNames::with_suffix(&config.suffix);
+        assert_eq!(names.bill, "t_sim_match_bill_2024_q1");
+        assert_eq!(names.bill_item, "t_sim_match_bill_item_2024_q1");
+        assert_eq!(names.invoice, "t_sim_vatinvoice_2024_q1");
+        assert_eq!(names.invoice_item, "t_sim_vatinvoice_item_2024_q1");
+        assert_eq!(names.result, "t_sim_match_result_2024_q1");
+        assert_eq!(names.stats, "t_sim_match_stats_2024_q1");
+    }
+
+    #[test]
+    fn injection_like_suffix_is_rejected_before_tables_are_set() {
+        for suffix in ["_1201; DROP TABLE t_sim_match_bill_1201", "_1201 --", "_12\"01", &"_".repeat(33)] {
+            let config = TableConfig { suffix: suffix.to_string() };
+            assert!(init(&config).is_err(), "{}", suffix);
+        }
+        // 被拒绝的后缀不会写入表名
+        assert!(!bill().contains(';'));
+    }
+
+    #[test]
+    fn default_suffix_is_1201() {
+        assert_eq!(TableConfig::default().suffix, "_1201");
+        // 测试中不会成功调用 init，表名使用默认后缀
+        assert_eq!(bill(), "t_sim_match_bill_1201");
+        assert_eq!(invoice_item(), "t_sim_vatinvoice_item_1201");
+    }
+}
@@ -4,6 +4,6 @@ pub mod db;
 pub mod models;
 pub mod service;
 
-pub use config::AppConfig;
+pub use config::{AppConfig, MatchOptions, MatchingConfig, MatchingConfigOverride, PoolConfig};
 pub use db::create_pool;
 pub use service::{MatcherService, InvoiceCentricMatcher};
@@ -1,19 +1,23 @@
-use axum::{routing::{get, post}, Router};
+use axum::{middleware, routing::{get, post}, Router};
+use std::future::IntoFuture;
 use std::sync::Arc;
-use tax_redflush_rust::{api, create_pool, AppConfig, MatcherService, InvoiceCentricMatcher};
+use std::time::Duration;
+use tax_redflush_rust::api::{self, AppState};
+use tax_redflush_rust::db::tables;
+#[cfg(feature = "metrics")]
+use tax_redflush_rust::service::metrics;
+use tax_redflush_rust::service::{CancellationToken, InFlightTracker, JobRegistry};
+use tax_redflush_rust::{create_pool, AppConfig, MatcherService, InvoiceCentricMatcher};
+use tokio::sync::Notify;
 use tower::ServiceBuilder;
 use tracing::info;
 use tracing_subscriber::fmt::time::ChronoLocal;
 
-/// 共享状态：包含两种匹配服务
-#[derive(Clone)]
-pub struct AppState {
-    pub sku_centric: Arc<MatcherService>,
-    pub invoice_centric: Arc<InvoiceCentricMatcher>,
-}
+/// 宽限期后取消进行中的匹配，再等待其到达检查点退出的时长
+const SHUTDOWN_CANCEL_DRAIN: Duration = Duration::from_secs(5);
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     // 初始化日志 - 使用本地时间格式 (类似Java格式)
     tracing_subscriber::fmt()
         .with_timer(ChronoLocal::new("%Y-%m-%d %H:%M:%S".to_string()))
@@ -24,31 +28,104 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // 加载配置
     let config = AppConfig::from_env();
     info!("Starting server with config: {:?}", config);
+    tables::init(&config.tables)?;
+
+    // Prometheus 指标记录器 (GET /metrics)
+    #[cfg(feature = "metrics")]
+    let metrics_handle = metrics::install_recorder()?;
+    #[cfg(feature = "metrics")]
+    {
+        let upkeep_handle = metrics_handle.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(5));
+            loop {
+                interval.tick().await;
+                upkeep_handle.run_upkeep();
+            }
+        });
+    }
 
     // 创建数据库连接池
-    let pool = create_pool(&config.database.url).await?;
+    let pool = create_pool(&config.database.url, &config.pool).await?;
     info!("Database pool created");
 
     // 创建两种匹配服务
-    let sku_centric_service = Arc::new(MatcherService::new(pool.clone()));
-    let invoice_centric_matcher = Arc::new(InvoiceCentricMatcher::new(pool));
+    let sku_centric_service = Arc::new(MatcherService::new(pool.clone(), config.matching.clone()));
+    let invoice_centric_matcher = Arc::new(InvoiceCentricMatcher::new(pool.clone(), config.matching.clone()));
 
-    // 构建路由
-    // 原SKU-Centric算法路由
-    let sku_centric_routes = Router::new()
-        .route("/api/match/batch", post(api::batch_match))
-        .with_state(sku_centric_service);
+    // 停机令牌: 匹配请求与异步任务的取消令牌均由其派生
+    let shutdown = CancellationToken::new();
+    let jobs = Arc::new(JobRegistry::new(shutdown.clone()));
+    let in_flight = Arc::new(InFlightTracker::new());
+    let state = AppState {
+        sku_centric: sku_centric_service,
+        progress: invoice_centric_matcher.progress(),
+        invoice_centric: invoice_centric_matcher,
+        jobs: jobs.clone(),
+        in_flight: in_flight.clone(),
+        #[cfg(feature = "metrics")]
+        metrics: metrics_handle,
+        pool,
+    };
 
-    // 新Invoice-Centric算法路由
-    let invoice_centric_routes = Router::new()
+    // 构建匹配路由
+    let match_routes: Router<AppState> = Router::new()
+        // 原SKU-Centric算法路由
+        .route("/api/match/batch", post(api::batch_match))
+        // 新Invoice-Centric算法路由
         .route("/api/match/batch/v2", post(api::batch_match_invoice_centric))
-        .with_state(invoice_centric_matcher);
+        // Invoice-Centric单个单据同步匹配
+        .route("/api/match/v2/:bill_id", get(api::match_single_bill_invoice_centric))
+        // Invoice-Centric单个单据匹配进度流 (SSE)
+        .route("/api/match/v2/:bill_id/stream", get(api::stream_single_bill_invoice_centric))
+        // 查询单据候选发票覆盖度 (只读，不做匹配)
+        .route("/api/match/v2/:bill_id/candidates", get(api::get_bill_candidate_coverage))
+        // 查询单据候选发票数 (只计数，匹配前预估规模)
+        .route("/api/match/v2/:bill_id/candidates/count", get(api::get_bill_candidate_count))
+        // Invoice-Centric按上传的单据ID文件批量匹配 (multipart 的 file 字段)
+        .route("/api/match/v2/upload", post(api::upload_match_invoice_centric))
+        // Invoice-Centric批量模拟，只返回汇总统计 (不写库、不导出)
+        .route("/api/match/v2/simulate", post(api::simulate_invoice_centric))
+        // Invoice-Centric异步批量匹配，立即返回任务ID
+        .route("/api/match/v2/async", post(api::submit_match_job))
+        // 查询异步任务状态
+        .route("/api/match/jobs/:job_id", get(api::get_match_job))
+        // 取消异步任务
+        .route("/api/match/jobs/:job_id/cancel", post(api::cancel_match_job))
+        // 两种算法发票重叠对比 (dry-run)
+        .route("/api/match/compare", post(api::compare_invoice_overlap))
+        // 查询已匹配单据的缺口报告
+        .route("/api/match/:bill_id/gaps", get(api::get_bill_gaps))
+        // 查询当前同步批量的进度
+        .route("/api/match/progress", get(api::get_match_progress));
+    // 匹配请求取消令牌 (停机时取消)，配置了时限时超时取消并返回 504
+    let request_timeout = api::RequestTimeout {
+        limit: config.server.request_timeout_secs.map(Duration::from_secs),
+        shutdown: shutdown.clone(),
+    };
+    let match_routes = match_routes.route_layer(middleware::from_fn_with_state(request_timeout, api::enforce_timeout));
+    // 进行中的匹配请求计数 (停机时等待其完成)
+    let match_routes = match_routes.route_layer(middleware::from_fn_with_state(in_flight.clone(), api::track_in_flight));
 
-    // 合并路由
-    let app = Router::new()
+    // 构建路由
+    let router: Router<AppState> = Router::new()
         .route("/health", get(api::health_check))
-        .merge(sku_centric_routes)
-        .merge(invoice_centric_routes)
+        // 构建信息 (不依赖数据库)
+        .route("/api/version", get(api::version))
+        .merge(match_routes)
+        // 查询单据候选发票明细 (排查用，不做匹配)
+        .route("/api/bills/:bill_id/candidates", get(api::get_bill_candidates));
+    // 管理诊断接口 (需开启 ENABLE_ADMIN_API)
+    let router = if config.server.admin_api {
+        router.route("/api/diag/explain/:bill_id", get(api::explain_candidate_queries))
+    } else {
+        router
+    };
+    // Prometheus 指标
+    #[cfg(feature = "metrics")]
+    let router = router.route("/metrics", get(api::metrics));
+    let app = router
+        .with_state(state)
         .layer(ServiceBuilder::new());
 
     // 启动服务器
@@ -57,9 +134,96 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     info!("API Endpoints:");
     info!("  POST /api/match/batch     - SKU-Centric (original)");
     info!("  POST /api/match/batch/v2  - Invoice-Centric (optimized)");
+    info!("  GET  /api/match/v2/:bill_id - Invoice-Centric, single bill");
+    info!("  GET  /api/match/v2/:bill_id/stream - Invoice-Centric, single bill with SSE progress");
+    info!("  GET  /api/match/v2/:bill_id/candidates - Candidate invoice coverage (read-only)");
+    info!("  GET  /api/match/v2/:bill_id/candidates/count - Candidate invoice and SKU counts (pre-flight)");
+    info!("  POST /api/match/v2/upload - Invoice-Centric, bill ids from an uploaded file");
+    info!("  POST /api/match/v2/simulate - Invoice-Centric, in-memory simulation (aggregate stats only)");
+    info!("  POST /api/match/v2/async  - Invoice-Centric, async job");
+    info!("  GET  /api/match/jobs/:job_id - Status of an async job");
+    info!("  POST /api/match/jobs/:job_id/cancel - Cancel an async job");
+    info!("  POST /api/match/compare   - Invoice overlap of both algorithms (dry-run)");
+    info!("  GET  /api/match/:bill_id/gaps - Gap report of a matched bill");
+    info!("  GET  /api/match/progress  - Progress of the running batch");
+    info!("  GET  /api/bills/:bill_id/candidates - Candidate invoice items of a bill (debug)");
+    if config.server.admin_api {
+        info!("  GET  /api/diag/explain/:bill_id - EXPLAIN ANALYZE of the candidate queries (admin)");
+    }
+    #[cfg(feature = "metrics")]
+    info!("  GET  /metrics             - Prometheus metrics");
 
     let listener = tokio::net::TcpListener::bind(&addr).await?;
-    axum::serve(listener, app).await?;
+    let stop_accepting = Arc::new(Notify::new());
+    let stop = stop_accepting.clone();
+    let mut server = tokio::spawn(
+        axum::serve(listener, app)
+            .with_graceful_shutdown(async move { stop.notified().await })
+            .into_future(),
+    );
+
+    tokio::select! {
+        result = &mut server => return Ok(result??),
+        _ = shutdown_signal() => {}
+    }
+
+    // 停机: 不再接受新请求，在宽限期内等待进行中的匹配完成，避免留下不完整的结果文件
+    let grace = Duration::from_secs(config.server.shutdown_grace_secs);
+    let pending = in_flight.active();
+    info!("收到停止信号, 不再接受新请求, 等待 {} 个进行中的匹配完成 (最多 {}s)", pending, grace.as_secs());
+    stop_accepting.notify_one();
+
+    if in_flight.wait_idle(grace).await {
+        info!("停机: {} 个进行中的匹配已全部完成", pending);
+    } else {
+        let cancelled = in_flight.active();
+        let cancelled_jobs = jobs.active_count();
+        shutdown.cancel();
+        tracing::warn!(
+            "停机: 宽限期已到, {} 个匹配已完成, 取消 {} 个未完成的匹配 (其中异步任务 {} 个), 等待其在检查点停止 (最多 {}s)",
+            pending.saturating_sub(cancelled), cancelled, cancelled_jobs, SHUTDOWN_CANCEL_DRAIN.as_secs()
+        );
+        // 取消后匹配在下一个检查点停止并清理未完成的输出，等待其退出后再中止服务
+        if in_flight.wait_idle(SHUTDOWN_CANCEL_DRAIN).await && jobs.active_count() == 0 {
+            info!("停机: {} 个被取消的匹配均已在检查点停止", cancelled);
+        } else {
+            tracing::warn!(
+                "停机: {} 个匹配 (其中异步任务 {} 个) 取消后仍未停止, 强制中止, 可能留下不完整的输出",
+                in_flight.active(), jobs.active_count()
+            );
+        }
+    }
+    server.abort();
 
     Ok(())
 }
+
+/// 等待停止信号: Ctrl-C，或 Unix 下的 SIGTERM（滚动发布时由容器平台发送）
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("监听 Ctrl-C 失败: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("监听 SIGTERM 失败: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
@@ -19,6 +19,10 @@ pub struct MatchBillItem1201 {
     pub famount: BigDecimal,  // 金额
     pub fnum: Option<BigDecimal>,      // 数量
     pub funitprice: Option<BigDecimal>, // 单价
+    /// 币种（可选，查询未返回该列时为 None）
+    #[sqlx(default)]
+    #[serde(default)]
+    pub fcurrency: Option<String>,
 }
 
 /// 临时汇总表 (用于SKU稀缺度排序)
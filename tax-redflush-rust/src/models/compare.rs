@@ -0,0 +1,202 @@
+use crate::models::MatchResult1201;
+use bigdecimal::{BigDecimal, Zero};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// 两种算法所选发票的重叠情况 (SKU-Centric vs Invoice-Centric)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InvoiceOverlap {
+    pub bill_id: i64,
+    pub common_invoices: Vec<i64>,      // 两种算法都使用的发票
+    pub only_sku_centric: Vec<i64>,     // 仅 SKU-Centric 使用的发票
+    pub only_invoice_centric: Vec<i64>, // 仅 Invoice-Centric 使用的发票
+}
+
+impl InvoiceOverlap {
+    /// 根据两种算法各自使用的发票ID计算重叠集合（结果按发票ID升序）
+    pub fn from_invoice_sets(bill_id: i64, sku_centric: &[i64], invoice_centric: &[i64]) -> Self {
+        let sku_set: BTreeSet<i64> = sku_centric.iter().copied().collect();
+        let invoice_set: BTreeSet<i64> = invoice_centric.iter().copied().collect();
+
+        Self {
+            bill_id,
+            common_invoices: sku_set.intersection(&invoice_set).copied().collect(),
+            only_sku_centric: sku_set.difference(&invoice_set).copied().collect(),
+            only_invoice_centric: invoice_set.difference(&sku_set).copied().collect(),
+        }
+    }
+}
+
+/// 按 (发票明细, SKU) 汇总的匹配金额
+#[derive(Debug, Clone, PartialEq, FromRow, Serialize, Deserialize)]
+pub struct MatchAllocation {
+    pub finvoiceid: i64,
+    pub finvoiceitemid: i64,
+    pub fspbm: String,
+    pub fmatchamount: BigDecimal,
+}
+
+/// 同一 (发票明细, SKU) 的匹配金额变化
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AllocationChange {
+    pub finvoiceid: i64,
+    pub finvoiceitemid: i64,
+    pub fspbm: String,
+    pub old_amount: BigDecimal,
+    pub new_amount: BigDecimal,
+}
+
+/// 重新匹配结果与数据库已有结果的差异（按发票明细ID + SKU 比对）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResultDiff {
+    pub bill_id: i64,
+    /// 仅新结果中存在
+    pub added: Vec<MatchAllocation>,
+    /// 仅已有结果中存在
+    pub removed: Vec<MatchAllocation>,
+    /// 两边都有但金额不同
+    pub changed: Vec<AllocationChange>,
+}
+
+impl ResultDiff {
+    /// 比对已有分配与新匹配结果（新结果先按 (发票明细, SKU) 汇总金额）
+    pub fn from_results(bill_id: i64, existing: &[MatchAllocation], new_results: &[MatchResult1201]) -> Self {
+        let mut new_map: BTreeMap<(i64, String), MatchAllocation> = BTreeMap::new();
+        for result in new_results {
+            new_map
+                .entry((result.finvoiceitemid, result.fspbm.clone()))
+                .or_insert_with(|| MatchAllocation {
+                    finvoiceid: result.finvoiceid,
+                    finvoiceitemid: result.finvoiceitemid,
+                    fspbm: result.fspbm.clone(),
+                    fmatchamount: BigDecimal::zero(),
+                })
+                .fmatchamount += &result.fmatchamount;
+        }
+
+        let mut old_map: BTreeMap<(i64, String), &MatchAllocation> = BTreeMap::new();
+        for allocation in existing {
+            old_map.insert((allocation.finvoiceitemid, allocation.fspbm.clone()), allocation);
+        }
+
+        let mut added = Vec::new();
+        let mut changed = Vec::new();
+        for (key, new_allocation) in &new_map {
+            match old_map.get(key) {
+                None => added.push(new_allocation.clone()),
+                Some(old_allocation) if old_allocation.fmatchamount != new_allocation.fmatchamount => {
+                    changed.push(AllocationChange {
+                        finvoiceid: new_allocation.finvoiceid,
+                        finvoiceitemid: new_allocation.finvoiceitemid,
+                        fspbm: new_allocation.fspbm.clone(),
+                        old_amount: old_allocation.fmatchamount.clone(),
+                        new_amount: new_allocation.fmatchamount.clone(),
+                    });
+                }
+                Some(_) => {}
+            }
+        }
+
+        let removed = old_map
+            .iter()
+            .filter(|(key, _)| !new_map.contains_key(*key))
+            .map(|(_, allocation)| (*allocation).clone())
+            .collect();
+
+        Self { bill_id, added, removed, changed }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use std::collections::HashMap;
+
+    fn allocation(invoice_id: i64, item_id: i64, sku: &str, amount: i64) -> MatchAllocation {
+        MatchAllocation {
+            finvoiceid: invoice_id,
+            finvoiceitemid: item_id,
+            fspbm: sku.to_string(),
+            fmatchamount: BigDecimal::from(amount),
+        }
+    }
+
+    fn result(invoice_id: i64, item_id: i64, sku: &str, amount: i64) -> MatchResult1201 {
+        MatchResult1201 {
+            fbillid: 7,
+            fbuyertaxno: "BUYER".to_string(),
+            fsalertaxno: "SELLER".to_string(),
+            fspbm: sku.to_string(),
+            finvoiceid: invoice_id,
+            finvoiceitemid: item_id,
+            fnum: BigDecimal::from(1),
+            fbillamount: BigDecimal::from(100),
+            finvoiceamount: BigDecimal::from(amount),
+            fmatchamount: BigDecimal::from(amount),
+            fbillunitprice: None,
+            fbillqty: None,
+            finvoiceunitprice: None,
+            finvoiceqty: None,
+            fmatchtime: Utc::now(),
+            extra: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn overlap_splits_divergent_invoice_choices() {
+        // SKU-Centric 逐 SKU 取余额最大的发票，Invoice-Centric 优先选一张覆盖多 SKU 的发票
+        let sku_centric = [101, 102, 103, 102];
+        let invoice_centric = [200, 103, 200];
+
+        let overlap = InvoiceOverlap::from_invoice_sets(7, &sku_centric, &invoice_centric);
+
+        assert_eq!(overlap.bill_id, 7);
+        assert_eq!(overlap.common_invoices, vec![103]);
+        assert_eq!(overlap.only_sku_centric, vec![101, 102]);
+        assert_eq!(overlap.only_invoice_centric, vec![200]);
+    }
+
+    #[test]
+    fn overlap_of_identical_choices_is_all_common() {
+        let overlap = InvoiceOverlap::from_invoice_sets(7, &[3, 1, 2], &[2, 3, 1]);
+
+        assert_eq!(overlap.common_invoices, vec![1, 2, 3]);
+        assert!(overlap.only_sku_centric.is_empty());
+        assert!(overlap.only_invoice_centric.is_empty());
+    }
+
+    #[test]
+    fn rerun_diff_captures_only_the_changed_allocation() {
+        let existing = [allocation(1, 11, "A", 60), allocation(1, 12, "B", 50), allocation(2, 21, "A", 40)];
+        // 明细11拆成两行但合计不变，只有明细21的金额变化
+        let rerun = [result(1, 11, "A", 30), result(1, 12, "B", 50), result(1, 11, "A", 30), result(2, 21, "A", 20)];
+
+        let diff = ResultDiff::from_results(7, &existing, &rerun);
+
+        assert!(diff.added.is_empty());
+        assert!(diff.removed.is_empty());
+        assert_eq!(diff.changed.len(), 1);
+        let change = &diff.changed[0];
+        assert_eq!((change.finvoiceid, change.finvoiceitemid, change.fspbm.as_str()), (2, 21, "A"));
+        assert_eq!((change.old_amount.clone(), change.new_amount.clone()), (BigDecimal::from(40), BigDecimal::from(20)));
+    }
+
+    #[test]
+    fn rerun_on_another_invoice_is_added_and_removed() {
+        let existing = [allocation(1, 11, "A", 60)];
+        let rerun = [result(3, 31, "A", 60)];
+
+        let diff = ResultDiff::from_results(7, &existing, &rerun);
+
+        assert_eq!(diff.added.iter().map(|a| a.finvoiceitemid).collect::<Vec<_>>(), vec![31]);
+        assert_eq!(diff.removed.iter().map(|a| a.finvoiceitemid).collect::<Vec<_>>(), vec![11]);
+        assert!(diff.changed.is_empty());
+        assert!(ResultDiff::from_results(7, &existing, &[result(1, 11, "A", 60)]).is_empty());
+    }
+}
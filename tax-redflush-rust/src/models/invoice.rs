@@ -13,9 +13,10 @@ pub struct MatchedInvoiceItem {
     pub unit_price: Option<BigDecimal>,
 }
 
-/// 候选发票统计结果
+/// 候选发票统计结果（按商品编码分组）
 #[derive(Debug, Clone, FromRow)]
 pub struct CandidateStat {
+    pub product_code: String,
     pub cnt: i64,
     pub sum_amount: BigDecimal,
 }
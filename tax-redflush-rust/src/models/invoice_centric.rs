@@ -1,23 +1,31 @@
-use bigdecimal::{BigDecimal, ToPrimitive};
+use crate::config::{DatePreference, MatchBy, MatchStrategy, SelectionMode, ZeroAmountPolicy};
+use crate::models::{normalize_product_code, SkuGap};
+use bigdecimal::{BigDecimal, ToPrimitive, Zero};
+use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
-use std::cmp::Ordering;
+use std::cmp::{Ordering, Reverse};
 use std::collections::{BinaryHeap, HashMap, HashSet};
 
 /// 发票评分（用于堆排序）
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct InvoiceScore {
     pub invoice_id: i64,
-    pub score: i64,      // 整数化评分 (amount * 100 + bonus)
+    pub remainder_rank: i128, // 剩余碎片排名 (首要优先级，越大越优先，见 SelectionMode；Coverage 模式恒为 0)
+    pub score: i128,     // 整数化评分 (amount * score_scale + bonus)
     pub sku_count: i64,  // 覆盖SKU数量 (第二优先级)
+    pub date_rank: i64,  // 开票时间偏好 (第三优先级，越大越优先，见 DatePreference)
 }
 
 impl Ord for InvoiceScore {
     fn cmp(&self, other: &Self) -> Ordering {
-        // 先按分数比较，分数相同按 SKU 数量比较
-        self.score
-            .cmp(&other.score)
+        // 先按剩余碎片排名，再按分数比较，分数相同按 SKU 数量、开票时间偏好比较，最后发票ID小的优先，保证结果稳定
+        self.remainder_rank
+            .cmp(&other.remainder_rank)
+            .then_with(|| self.score.cmp(&other.score))
             .then_with(|| self.sku_count.cmp(&other.sku_count))
+            .then_with(|| self.date_rank.cmp(&other.date_rank))
+            .then_with(|| other.invoice_id.cmp(&self.invoice_id))
     }
 }
 
@@ -27,6 +35,30 @@ impl PartialOrd for InvoiceScore {
     }
 }
 
+/// 发票评分分解 - 各分量之和即总评分
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ScoreBreakdown {
+    /// 可匹配金额分量 (amount * score_scale)
+    pub amount_component: i128,
+    /// 稀缺性加分分量
+    pub scarcity_component: i128,
+    /// 整单红冲加分分量（完美红冲或子集红冲）
+    pub flush_component: i128,
+    /// 覆盖SKU数量
+    pub sku_count: i64,
+    /// 按当前需求匹配后发票剩余的金额 (remaining - 可匹配, 按 score_scale 缩放)，不计入总评分
+    pub leftover: i128,
+}
+
+impl ScoreBreakdown {
+    /// 总评分
+    pub fn total(&self) -> i128 {
+        self.amount_component
+            .saturating_add(self.scarcity_component)
+            .saturating_add(self.flush_component)
+    }
+}
+
 /// 发票覆盖度统计 - 用于查询结果
 #[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
 pub struct InvoiceCoverage {
@@ -44,6 +76,18 @@ pub struct InvoiceItemDetail {
     pub quantity: BigDecimal,
     pub amount: BigDecimal,
     pub unit_price: Option<BigDecimal>,
+    /// 币种（可选，查询未返回该列时为 None）
+    #[sqlx(default)]
+    #[serde(default)]
+    pub currency: Option<String>,
+    /// 发票开票时间（可选，用于按开票时间先后打破评分平局）
+    #[sqlx(default)]
+    #[serde(default)]
+    pub issue_time: Option<NaiveDateTime>,
+    /// 发票表头总金额（可选，同一发票各明细累计匹配金额不超过该值）
+    #[sqlx(default)]
+    #[serde(default)]
+    pub invoice_total: Option<BigDecimal>,
 }
 
 /// 发票明细状态 - 追踪每个明细的剩余可用金额
@@ -58,6 +102,16 @@ pub struct InvoiceItemState {
     pub unit_price: Option<BigDecimal>,
 }
 
+impl InvoiceItemState {
+    /// 按明细原始金额/数量比例折算某金额对应的数量（数量或金额缺失时返回 None）
+    pub fn quantity_for_amount(&self, amount: &BigDecimal) -> Option<BigDecimal> {
+        if self.original_amount.is_zero() || self.quantity.is_zero() {
+            return None;
+        }
+        Some(amount * self.quantity.abs() / self.original_amount.abs())
+    }
+}
+
 /// 发票及其所有明细
 #[derive(Debug, Clone)]
 pub struct InvoiceWithItems {
@@ -105,27 +159,169 @@ impl InvoiceWithItems {
 #[derive(Debug, Clone)]
 pub struct MatchingRequirements {
     requirements: HashMap<String, BigDecimal>,
+    /// 因SKU为空/空白被跳过的单据明细行数
+    skipped_blank_skus: usize,
+    /// 需求下限: 扣减后剩余低于该值的SKU视为已满足，剩余部分记为可忽略缺口
+    floor: Option<BigDecimal>,
+    /// 低于需求下限而提前关闭的SKU剩余金额
+    negligible: HashMap<String, BigDecimal>,
+    /// 金额容差: 剩余不超过该值即视为已满足（吸收金额舍入残差）
+    tolerance: BigDecimal,
+    /// SKU剩余需求数量（仅所有明细都有数量的SKU）
+    quantities: HashMap<String, BigDecimal>,
+    /// 双重约束: 同时按金额与数量扣减需求
+    dual_constraint: bool,
+    /// 数量已耗尽而提前关闭的SKU剩余金额（计入缺口）
+    quantity_capped: HashMap<String, BigDecimal>,
+    /// 单据中与归一化结果不同的原始商品编码（去除首尾空白后），查询数据库时一并带上
+    source_codes: HashSet<String>,
 }
 
 impl MatchingRequirements {
     pub fn new() -> Self {
         Self {
             requirements: HashMap::new(),
+            skipped_blank_skus: 0,
+            floor: None,
+            negligible: HashMap::new(),
+            tolerance: BigDecimal::from(0),
+            quantities: HashMap::new(),
+            dual_constraint: false,
+            quantity_capped: HashMap::new(),
+            source_codes: HashSet::new(),
         }
     }
 
     /// 从单据明细构建需求
-    pub fn from_bill_items(bill_items: &[crate::models::MatchBillItem1201]) -> Self {
+    /// 金额为 0 的明细按 zero_amount_policy 处理，Error 策略下遇到即返回错误
+    pub fn from_bill_items(
+        bill_items: &[crate::models::MatchBillItem1201],
+        zero_amount_policy: ZeroAmountPolicy,
+    ) -> Result<Self, String> {
+        Self::from_bill_items_by(bill_items, zero_amount_policy, MatchBy::Amount)
+    }
+
+    /// 按指定维度从单据明细构建需求
+    /// 按数量时需求取明细数量（缺少数量按 0 处理），不做数量约束
+    pub fn from_bill_items_by(
+        bill_items: &[crate::models::MatchBillItem1201],
+        zero_amount_policy: ZeroAmountPolicy,
+        match_by: MatchBy,
+    ) -> Result<Self, String> {
         let mut requirements = HashMap::new();
+        let mut quantities: HashMap<String, BigDecimal> = HashMap::new();
+        let mut missing_quantity: HashSet<String> = HashSet::new();
+        let mut source_codes: HashSet<String> = HashSet::new();
+        let mut skipped_blank_skus = 0;
         for item in bill_items {
-            let sku = item.fspbm.trim();
+            let sku = normalize_product_code(&item.fspbm);
             if sku.is_empty() {
+                skipped_blank_skus += 1;
                 continue;
             }
-            let amount = item.famount.abs();
-            *requirements.entry(sku.to_string()).or_insert_with(|| BigDecimal::from(0)) += amount;
+            if sku != item.fspbm.trim() {
+                source_codes.insert(item.fspbm.trim().to_string());
+            }
+            let (value, label) = match match_by {
+                MatchBy::Amount => (item.famount.clone(), "金额"),
+                MatchBy::Quantity => (item.fnum.clone().unwrap_or_else(BigDecimal::zero), "数量"),
+            };
+            if value.is_zero() {
+                match zero_amount_policy {
+                    ZeroAmountPolicy::Skip => continue,
+                    ZeroAmountPolicy::Keep => {}
+                    ZeroAmountPolicy::Error => {
+                        return Err(format!("单据明细 {} (SKU {}) {}为 0", item.fentryid, sku, label));
+                    }
+                }
+            }
+            let amount = value.abs();
+            *requirements.entry(sku.clone()).or_insert_with(|| BigDecimal::from(0)) += amount;
+            match &item.fnum {
+                Some(num) if !num.is_zero() => {
+                    *quantities.entry(sku.clone()).or_insert_with(|| BigDecimal::from(0)) += num.abs();
+                }
+                _ => {
+                    missing_quantity.insert(sku);
+                }
+            }
+        }
+        // 任一明细缺少数量的SKU不做数量约束；按数量匹配时需求本身就是数量
+        quantities.retain(|sku, _| match_by == MatchBy::Amount && !missing_quantity.contains(sku));
+        Ok(Self {
+            requirements,
+            skipped_blank_skus,
+            floor: None,
+            negligible: HashMap::new(),
+            tolerance: BigDecimal::from(0),
+            quantities,
+            dual_constraint: false,
+            quantity_capped: HashMap::new(),
+            source_codes,
+        })
+    }
+
+    /// 启用/关闭双重约束（金额 + 数量）
+    pub fn set_dual_constraint(&mut self, enabled: bool) {
+        self.dual_constraint = enabled;
+    }
+
+    /// 双重约束下，发票明细按其单价折算剩余需求数量后可匹配的金额上限
+    /// 未启用双重约束、SKU没有数量需求或明细缺少数量时返回 None（不限制）
+    pub fn quantity_amount_cap(&self, item: &InvoiceItemState) -> Option<BigDecimal> {
+        if !self.dual_constraint || item.quantity.is_zero() {
+            return None;
+        }
+        let remaining_qty = self.quantities.get(&item.product_code)?;
+        Some(remaining_qty * item.original_amount.abs() / item.quantity.abs())
+    }
+
+    /// 扣减某SKU的需求数量（仅双重约束下生效）
+    /// 数量耗尽时关闭该SKU，剩余金额低于需求下限记为可忽略缺口，否则计入缺口
+    pub fn reduce_quantity(&mut self, sku: &str, quantity: &BigDecimal) {
+        if !self.dual_constraint {
+            return;
+        }
+        let Some(remaining_qty) = self.quantities.get_mut(sku) else {
+            return;
+        };
+        *remaining_qty = &*remaining_qty - quantity;
+        if *remaining_qty > BigDecimal::from(0) {
+            return;
+        }
+        self.quantities.remove(sku);
+        if let Some(remaining) = self.requirements.remove(sku) {
+            if remaining <= self.tolerance {
+                return;
+            }
+            if self.floor.as_ref().is_some_and(|floor| remaining < *floor) {
+                self.negligible.insert(sku.to_string(), remaining);
+            } else {
+                self.quantity_capped.insert(sku.to_string(), remaining);
+            }
         }
-        Self { requirements }
+    }
+
+    /// 设置需求下限 (None 表示不启用)
+    pub fn set_floor(&mut self, floor: Option<BigDecimal>) {
+        self.floor = floor.filter(|f| *f > BigDecimal::from(0));
+    }
+
+    /// 设置金额容差 (None 或非正数表示不启用)
+    pub fn set_tolerance(&mut self, tolerance: Option<BigDecimal>) {
+        self.tolerance = tolerance
+            .filter(|t| *t > BigDecimal::from(0))
+            .unwrap_or_else(|| BigDecimal::from(0));
+    }
+
+    /// 金额容差（未启用时为 0）
+    pub fn tolerance(&self) -> &BigDecimal {
+        &self.tolerance
+    }
+
+    /// 因SKU为空/空白被跳过的单据明细行数
+    pub fn skipped_blank_skus(&self) -> usize {
+        self.skipped_blank_skus
     }
 
     /// 获取所有需要的SKU列表
@@ -133,35 +329,80 @@ impl MatchingRequirements {
         self.requirements.keys().cloned().collect()
     }
 
+    /// 查询候选发票用的商品编码: 归一化编码加上单据中的原始写法
+    /// 数据库按原值精确匹配，带上原始写法避免归一化后查不到原来能匹配的发票
+    pub fn query_skus(&self) -> Vec<String> {
+        self.requirements
+            .keys()
+            .cloned()
+            .chain(self.source_codes.iter().cloned())
+            .collect()
+    }
+
     /// 获取某SKU的剩余需求金额
     pub fn get_remaining(&self, sku: &str) -> Option<&BigDecimal> {
         self.requirements.get(sku)
     }
 
     /// 扣减某SKU的需求金额
+    /// 剩余不超过金额容差时视为已满足；低于需求下限时关闭该SKU，剩余金额记为可忽略缺口
     pub fn reduce(&mut self, sku: &str, amount: &BigDecimal) {
         if let Some(remaining) = self.requirements.get_mut(sku) {
             *remaining = &*remaining - amount;
-            if *remaining <= BigDecimal::from(0) {
+            if *remaining <= self.tolerance {
                 self.requirements.remove(sku);
+            } else if self.floor.as_ref().is_some_and(|floor| *remaining < *floor) {
+                if let Some(negligible) = self.requirements.remove(sku) {
+                    self.negligible.insert(sku.to_string(), negligible);
+                }
             }
         }
     }
 
+    /// 撤销已扣减的需求金额（整SKU模式回滚部分匹配时使用），SKU因数量耗尽关闭时加回缺口
+    pub fn reopen(&mut self, sku: &str, amount: &BigDecimal) {
+        if let Some(remaining) = self.quantity_capped.get_mut(sku) {
+            *remaining += amount;
+        } else {
+            *self.requirements.entry(sku.to_string()).or_insert_with(|| BigDecimal::from(0)) += amount;
+        }
+    }
+
+    /// 低于需求下限而提前关闭的SKU详情 (SKU, Amount)
+    pub fn get_negligible_details(&self) -> Vec<(String, BigDecimal)> {
+        self.negligible
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect()
+    }
+
+    /// 剩余需求超过金额容差的SKU（本身就不超过容差的需求视为已满足）
+    fn open_requirements(&self) -> impl Iterator<Item = (&String, &BigDecimal)> {
+        self.requirements.iter().filter(|(_, remaining)| **remaining > self.tolerance)
+    }
+
     /// 检查是否所有需求都已满足
     pub fn is_satisfied(&self) -> bool {
-        self.requirements.is_empty()
+        self.open_requirements().next().is_none()
     }
 
     /// 获取剩余未满足的SKU数量
     pub fn remaining_sku_count(&self) -> usize {
-        self.requirements.len()
+        self.open_requirements().count()
     }
 
-    /// 获取剩余未满足的SKU详情 (SKU, Amount)
-    pub fn get_remaining_details(&self) -> Vec<(String, BigDecimal)> {
+    /// 获取所有SKU剩余需求金额之和（含数量耗尽而关闭的SKU）
+    pub fn total_remaining_amount(&self) -> BigDecimal {
         self.requirements
-            .iter()
+            .values()
+            .chain(self.quantity_capped.values())
+            .fold(BigDecimal::from(0), |acc, v| acc + v)
+    }
+
+    /// 获取剩余未满足的SKU详情 (SKU, Amount)，含数量耗尽而关闭的SKU
+    pub fn get_remaining_details(&self) -> Vec<(String, BigDecimal)> {
+        self.open_requirements()
+            .chain(self.quantity_capped.iter())
             .map(|(k, v)| (k.clone(), v.clone()))
             .collect()
     }
@@ -184,8 +425,26 @@ pub struct InvoiceScoringContext {
     sku_frequency_map: HashMap<String, i64>,
     /// 已使用过的发票（用于统计，不影响复用）
     used_invoices: HashSet<i64>,
+    /// 已退出候选的发票（ConsumeOnce 策略下选中一次即退出）
+    retired_invoices: HashSet<i64>,
     /// 惰性堆 (Lazy Heap) - 缓存发票评分
     heap: BinaryHeap<InvoiceScore>,
+    /// 整数化评分的金额缩放倍数 (默认 100，即精确到分)
+    score_scale: i64,
+    /// 因SKU为空/空白被跳过的发票明细行数
+    skipped_blank_skus: usize,
+    /// 堆容量上限，仅保留评分最高的 K 张发票 (None 表示不限)
+    heap_capacity: Option<usize>,
+    /// 发票开票时间（明细带有开票时间时记录）
+    issue_times: HashMap<i64, NaiveDateTime>,
+    /// 发票表头总金额的剩余额度（明细带有表头总金额时记录），各明细累计消费不超过该值
+    invoice_caps: HashMap<i64, BigDecimal>,
+    /// 评分平局时的开票时间偏好
+    date_preference: DatePreference,
+    /// 贪心选票的发票排序方式
+    selection_mode: SelectionMode,
+    /// 被截断（溢出）发票的最高评分，是其当前评分的上界；0 表示没有溢出
+    spill_ceiling: i128,
     // 对发票评分的缓存检查机制 (Lazy Check 不需要复杂版本号，直接重算对比即可，
     // 但为了极致性能，我们可以记录上次计算时的 remaining_sku_count 或类似标记，
     // 这里简化逻辑：Pop出来 -> Re-calculate -> 比较 -> If dropped, push back)
@@ -198,26 +457,46 @@ impl InvoiceScoringContext {
             sku_invoice_index: HashMap::new(),
             sku_frequency_map: HashMap::new(),
             used_invoices: HashSet::new(),
+            retired_invoices: HashSet::new(),
             heap: BinaryHeap::new(),
+            score_scale: 100,
+            skipped_blank_skus: 0,
+            heap_capacity: None,
+            spill_ceiling: 0,
+            issue_times: HashMap::new(),
+            invoice_caps: HashMap::new(),
+            date_preference: DatePreference::Off,
+            selection_mode: SelectionMode::Coverage,
         }
     }
 
     /// 从发票明细列表构建上下文，同时创建倒排索引和频率表
     pub fn from_items(items: Vec<InvoiceItemDetail>) -> Self {
-        let mut invoices: HashMap<i64, Vec<InvoiceItemState>> = HashMap::new();
-        let mut sku_invoice_index: HashMap<String, HashSet<i64>> = HashMap::new();
-        let mut sku_frequency_map: HashMap<String, i64> = HashMap::new();
+        let mut context = Self::new();
+        context.add_items(items);
+        context
+    }
 
+    /// 追加发票明细（同步更新倒排索引和频率表），追加后需重新 init_heap
+    pub fn add_items(&mut self, items: Vec<InvoiceItemDetail>) {
         for item in items {
-            let sku = item.product_code.trim();
+            let sku = normalize_product_code(&item.product_code);
             if sku.is_empty() {
+                self.skipped_blank_skus += 1;
                 continue;
             }
 
+            if let Some(issue_time) = item.issue_time {
+                self.issue_times.insert(item.invoice_id, issue_time);
+            }
+            if let Some(total) = item.invoice_total.as_ref().filter(|total| **total > BigDecimal::from(0)) {
+                self.invoice_caps.entry(item.invoice_id).or_insert_with(|| total.clone());
+            }
+
             let state = InvoiceItemState {
                 invoice_id: item.invoice_id,
                 item_id: item.item_id,
-                product_code: sku.to_string(),
+                product_code: sku,
                 quantity: item.quantity,
                 original_amount: item.amount.clone(),
                 remaining_amount: item.amount,  // 初始时剩余金额 = 原始金额
@@ -225,33 +504,73 @@ impl InvoiceScoringContext {
             };
 
             // 更新倒排索引
-            if sku_invoice_index
+            if self.sku_invoice_index
                 .entry(state.product_code.clone())
-                .or_insert_with(HashSet::new)
+                .or_default()
                 .insert(state.invoice_id) {
                     // 仅当是新发票包含此SKU时，增加频率计数
-                    *sku_frequency_map.entry(state.product_code.clone()).or_insert(0) += 1;
+                    *self.sku_frequency_map.entry(state.product_code.clone()).or_insert(0) += 1;
                 }
 
             // 添加到发票明细列表
-            invoices
+            self.invoices
                 .entry(state.invoice_id)
-                .or_insert_with(Vec::new)
+                .or_default()
                 .push(state);
         }
+    }
 
-        Self {
-            invoices,
-            sku_invoice_index,
-            sku_frequency_map,
-            used_invoices: HashSet::new(),
-            heap: BinaryHeap::new(),
+    /// 因SKU为空/空白被跳过的发票明细行数
+    pub fn skipped_blank_skus(&self) -> usize {
+        self.skipped_blank_skus
+    }
+
+    /// 设置整数化评分的金额缩放倍数（需在 init_heap 之前调用）
+    pub fn set_score_scale(&mut self, scale: i64) {
+        self.score_scale = scale.max(1);
+    }
+
+    /// 设置评分平局时的开票时间偏好（需在 init_heap 之前调用）
+    pub fn set_date_preference(&mut self, preference: DatePreference) {
+        self.date_preference = preference;
+    }
+
+    /// 设置贪心选票的发票排序方式（需在 init_heap 之前调用）
+    pub fn set_selection_mode(&mut self, mode: SelectionMode) {
+        self.selection_mode = mode;
+    }
+
+    /// 剩余碎片排名，越大越优先；需求减少时剩余只增不减，排名单调不增，惰性检查仍然成立
+    fn remainder_rank(&self, breakdown: &ScoreBreakdown) -> i128 {
+        match self.selection_mode {
+            SelectionMode::Coverage => 0,
+            SelectionMode::SmallestRemainder => breakdown.leftover.saturating_neg(),
         }
     }
 
+    /// 开票时间偏好排名，越大越优先；不启用或发票缺少开票时间时排在最后
+    fn date_rank(&self, invoice_id: i64) -> i64 {
+        let Some(issue_time) = self.issue_times.get(&invoice_id) else {
+            return i64::MIN;
+        };
+        let timestamp = issue_time.and_utc().timestamp();
+        match self.date_preference {
+            DatePreference::Off => i64::MIN,
+            DatePreference::Fifo => -timestamp,
+            DatePreference::Lifo => timestamp,
+        }
+    }
+
+    /// 设置堆容量上限（需在 init_heap 之前调用）
+    pub fn set_heap_capacity(&mut self, capacity: Option<usize>) {
+        self.heap_capacity = capacity.filter(|&k| k > 0);
+    }
+
     /// 初始化堆（第一轮全量计算）
+    /// 设置了堆容量时只保留评分最高的 K 张发票，其余溢出，记录溢出评分上界
     pub fn init_heap(&mut self, requirements: &MatchingRequirements) {
         self.heap.clear();
+        self.spill_ceiling = 0;
         
         // 收集所有相关候选发票（只查有需求SKU的）
         let mut candidates: HashSet<i64> = HashSet::new();
@@ -261,33 +580,71 @@ impl InvoiceScoringContext {
             }
         }
 
+        // 有容量上限时用小顶堆筛选 Top-K
+        let mut top_k: BinaryHeap<Reverse<InvoiceScore>> = BinaryHeap::new();
+
         for invoice_id in candidates {
-            let (score, sku_count) = self.calculate_score_int(invoice_id, requirements);
-            if score > 0 {
-                self.heap.push(InvoiceScore {
-                    invoice_id,
-                    score,
-                    sku_count,
-                });
+            let breakdown = self.calculate_score_int(invoice_id, requirements);
+            let score = breakdown.total();
+            if score <= 0 {
+                continue;
             }
+            let entry = InvoiceScore {
+                invoice_id,
+                remainder_rank: self.remainder_rank(&breakdown),
+                score,
+                sku_count: breakdown.sku_count,
+                date_rank: self.date_rank(invoice_id),
+            };
+            match self.heap_capacity {
+                Some(capacity) => {
+                    top_k.push(Reverse(entry));
+                    if top_k.len() > capacity {
+                        if let Some(Reverse(spilled)) = top_k.pop() {
+                            self.spill_ceiling = self.spill_ceiling.max(spilled.score);
+                        }
+                    }
+                }
+                None => self.heap.push(entry),
+            }
+        }
+
+        if self.heap_capacity.is_some() {
+            self.heap = top_k.into_iter().map(|Reverse(entry)| entry).collect();
         }
     }
 
+    /// 溢出发票是否可能优于堆内发票（堆空或堆顶评分低于溢出评分上界）
+    fn should_readmit_spilled(&self) -> bool {
+        self.spill_ceiling > 0 && self.heap.peek().is_none_or(|top| top.score < self.spill_ceiling)
+    }
+
     /// 查找最优发票 - (Lazy Greed Strategy)
     pub fn find_best_invoice_lazy(&mut self, requirements: &MatchingRequirements) -> Option<i64> {
         loop {
+            // 0. 有界堆: Top-K 评分降到溢出上界以下时，按当前需求重建堆以回收溢出发票
+            if self.should_readmit_spilled() {
+                self.init_heap(requirements);
+            }
+
             // 1. 取出堆顶（当前认为最好的）
-            let best_candidate = match self.heap.pop() {
-                Some(c) => c,
-                None => return None, // 堆空了，没发票了
-            };
+            // 堆空了，没发票了
+            let best_candidate = self.heap.pop()?;
 
             // 2. 惰性检查 (Lazy Check)
             // 重新计算它的真实评分
-            let (current_score, current_sku_count) = self.calculate_score_int(best_candidate.invoice_id, requirements);
+            let breakdown = self.calculate_score_int(best_candidate.invoice_id, requirements);
+            let current_score = breakdown.total();
+            let current = InvoiceScore {
+                invoice_id: best_candidate.invoice_id,
+                remainder_rank: self.remainder_rank(&breakdown),
+                score: current_score,
+                sku_count: breakdown.sku_count,
+                date_rank: best_candidate.date_rank,
+            };
 
             // 3. 比较
-            // 如果堆已经是空的，或者 当前评分 >= 堆顶评分，说明它就是冠军！
+            // 如果堆已经是空的，或者 当前评分 >= 堆顶评分（平局按 SKU 数量、开票时间偏好比较），说明它就是冠军！
             // (注意：InvoiceScore 实现的是 Max-Heap，pop 出来的是最大的。
             // 只有当重新计算后的分数比堆里第二名还要小的时候，才需要放回去重新排。)
             
@@ -302,7 +659,7 @@ impl InvoiceScoringContext {
                     }
                 }
                 Some(second_best) => {
-                    if current_score >= second_best.score {
+                    if current >= *second_best {
                         // 依然比第二名强 (或者相等)，它就是冠军
                         if current_score > 0 {
                              return Some(best_candidate.invoice_id);
@@ -312,11 +669,7 @@ impl InvoiceScoringContext {
                     } else {
                         // 4. 它变弱了，退回去重新排队
                          if current_score > 0 {
-                            self.heap.push(InvoiceScore {
-                                invoice_id: best_candidate.invoice_id,
-                                score: current_score,
-                                sku_count: current_sku_count,
-                            });
+                            self.heap.push(current);
                         }
                         // 继续 loop，处理下一个堆顶
                     }
@@ -328,16 +681,32 @@ impl InvoiceScoringContext {
     // 保留原方法用于兼容或对比（可选，目前直接替换调用）
     // pub fn find_best_invoice(...) 
 
+    /// 按当前需求计算发票评分分解（用于审计）
+    pub fn score_breakdown(&self, invoice_id: i64, requirements: &MatchingRequirements) -> ScoreBreakdown {
+        self.calculate_score_int(invoice_id, requirements)
+    }
+
     /// 计算整数评分 (Integer Arithmetic Optimization)
-    /// 返回 (Score, SkuCount)
-    fn calculate_score_int(&self, invoice_id: i64, requirements: &MatchingRequirements) -> (i64, i64) {
+    /// 金额按 score_scale 缩放后取整，使用 i128 累加并饱和处理溢出
+    /// i128 可容纳约 1.7e38 / score_scale 的金额，饱和只会出现在远超实际业务的金额上，
+    /// 饱和后评分相同的发票按覆盖SKU数排序，不会因回绕让小额发票胜出
+    /// 返回评分分解，总评分为各分量之和
+    fn calculate_score_int(&self, invoice_id: i64, requirements: &MatchingRequirements) -> ScoreBreakdown {
+        // 已退出候选的发票不再参与评分，惰性堆弹出时会被直接丢弃
+        if self.retired_invoices.contains(&invoice_id) {
+            return ScoreBreakdown::default();
+        }
+
          let items = match self.invoices.get(&invoice_id) {
             Some(i) => i,
-            None => return (0, 0),
+            None => return ScoreBreakdown::default(),
         };
 
+        let scale = i128::from(self.score_scale);
         let mut sku_count = 0i64;
-        let mut score: i64 = 0;
+        let mut amount_component: i128 = 0;
+        let mut scarcity_component: i128 = 0;
+        let mut leftover: i128 = 0;
         
         // 检查是否整张发票都能被红冲 (Full Flush)
         // 条件：发票上所有剩余金额 > 0 的明细，都能找到需求，且需求量 >= 剩余量 (即会被耗尽)
@@ -362,42 +731,62 @@ impl InvoiceScoringContext {
                     } else {
                         required
                     };
+                    // 双重约束: 不超过剩余需求数量折算的金额
+                    let quantity_cap = requirements.quantity_amount_cap(item);
+                    let available = match &quantity_cap {
+                        Some(cap) if cap < available => cap,
+                        _ => available,
+                    };
                     
-                    // 整数化: available * 100
-                    if let Some(cent_val) = (available * BigDecimal::from(100)).to_i64() {
-                        score += cent_val;
-                    }
+                    // 整数化: available * score_scale
+                    // 超出 i128 范围时按上限计，保留该项对排序的作用而不是丢弃
+                    let scaled_val = scaled_to_i128(available, self.score_scale).unwrap_or(i128::MAX);
+                    amount_component = amount_component.saturating_add(scaled_val);
+                    leftover = leftover.saturating_add(self.scale_amount(&(&item.remaining_amount - available)));
 
                     // 稀缺性加分
                     if let Some(&freq) = self.sku_frequency_map.get(&item.product_code) {
                         if freq > 0 {
-                            let bonus = (1000 / freq) * 100; 
-                            score += bonus;
+                            let bonus = i128::from(1000 / freq) * scale;
+                            scarcity_component = scarcity_component.saturating_add(bonus);
                         }
                     }
                     
                     // 关键检查：是否能被耗尽？
                     // 如果 需求量 < 剩余量，说明没法耗尽这条明细，不满足 Full Flush
-                    if *required < item.remaining_amount {
+                    if *required < item.remaining_amount
+                        || quantity_cap.as_ref().is_some_and(|cap| *cap < item.remaining_amount)
+                    {
                         is_full_flush = false;
                     }
                 } else {
                     // 找到了需求记录但需求量为0 (实际上已被满足)，这条明细无法被消耗 -> 破坏 Full Flush
                      is_full_flush = false;
+                     leftover = leftover.saturating_add(self.scale_amount(&item.remaining_amount));
                 }
             } else {
                 // 根本没有需求 -> 破坏 Full Flush
                 is_full_flush = false;
+                leftover = leftover.saturating_add(self.scale_amount(&item.remaining_amount));
             }
         }
 
         if !has_valid_items {
-            return (0, 0);
+            return ScoreBreakdown::default();
+        }
+
+        // 可匹配金额不超过发票表头总金额的剩余额度，额度用尽的发票不再参与评分
+        if let Some(cap) = self.invoice_caps.get(&invoice_id) {
+            if *cap <= BigDecimal::from(0) {
+                return ScoreBreakdown::default();
+            }
+            let scaled_cap = scaled_to_i128(cap, self.score_scale).unwrap_or(i128::MAX);
+            amount_component = amount_component.min(scaled_cap);
         }
 
         // Apply Full Flush Bonus
         // 策略 V3: 区分 "完美红冲" (Perfect Full Flush) 和 "子集红冲" (Subset Full Flush)
-        // 1. 完美红冲 (Inv == Req): 既清空发票又清空需求。这是最优解，给予巨大奖励 (50M)。
+        // 1. 完美红冲 (Inv == Req): 既清空发票又清空需求。这是最优解，给予巨大奖励 (500,000 * score_scale，默认 50M)。
         // 2. 子集红冲 (Inv < Req): 清空发票但需求未满。这会导致碎片化 (需要更多发票)。
         //    给予较小奖励 (20%) 作为 Tie-breaker，但不要压倒大金额的非整单匹配。
         
@@ -418,30 +807,54 @@ impl InvoiceScoringContext {
            }
         }
 
-        if is_perfect_flush && has_valid_items {
-            score += 50_000_000;
+        let flush_component = if is_perfect_flush && has_valid_items {
+            scale.saturating_mul(500_000)
         } else if is_full_flush {
-            score += score / 5; // 20% bonus for subset flush
+            amount_component.saturating_add(scarcity_component) / 5 // 20% bonus for subset flush
+        } else {
+            0
+        };
+
+        ScoreBreakdown {
+            amount_component,
+            scarcity_component,
+            flush_component,
+            sku_count,
+            leftover,
         }
+    }
 
-        (score, sku_count)
+    /// 金额按 score_scale 缩放取整，超出 i128 范围时按上限计
+    fn scale_amount(&self, amount: &BigDecimal) -> i128 {
+        scaled_to_i128(amount, self.score_scale).unwrap_or(i128::MAX)
     }
 
 
     /// 消费明细金额（不标记整个发票为已使用）
+    /// 不超过发票表头总金额的剩余额度，额度用尽时不消费并返回 None
     pub fn consume_item(&mut self, invoice_id: i64, product_code: &str, amount: &BigDecimal) -> Option<InvoiceItemState> {
+        let cap = self.invoice_caps.get(&invoice_id).cloned();
+        if cap.as_ref().is_some_and(|cap| *cap <= BigDecimal::from(0)) {
+            return None;
+        }
         self.used_invoices.insert(invoice_id);  // 记录使用过
 
         if let Some(items) = self.invoices.get_mut(&invoice_id) {
             for item in items.iter_mut() {
                 if item.product_code == product_code && item.remaining_amount > BigDecimal::from(0) {
-                    let consumed = if *amount < item.remaining_amount {
+                    let mut consumed = if *amount < item.remaining_amount {
                         amount.clone()
                     } else {
                         item.remaining_amount.clone()
                     };
+                    if let Some(cap) = cap.as_ref().filter(|cap| **cap < consumed) {
+                        consumed = cap.clone();
+                    }
 
                     item.remaining_amount -= &consumed;
+                    if let Some(cap) = self.invoice_caps.get_mut(&invoice_id) {
+                        *cap -= &consumed;
+                    }
                     return Some(item.clone());
                 }
             }
@@ -450,6 +863,51 @@ impl InvoiceScoringContext {
         None
     }
 
+    /// 归还明细已消费的金额（整SKU模式回滚时使用），同时恢复发票表头额度
+    /// 发票的明细都恢复到原始金额时不再计入已使用发票
+    pub fn release_item(&mut self, invoice_id: i64, item_id: i64, amount: &BigDecimal) {
+        let Some(items) = self.invoices.get_mut(&invoice_id) else {
+            return;
+        };
+        if let Some(item) = items.iter_mut().find(|item| item.item_id == item_id) {
+            item.remaining_amount += amount;
+            if let Some(cap) = self.invoice_caps.get_mut(&invoice_id) {
+                *cap += amount;
+            }
+        }
+        if items.iter().all(|item| item.remaining_amount >= item.original_amount) {
+            self.used_invoices.remove(&invoice_id);
+        }
+    }
+
+    /// 发票表头总金额的剩余额度（明细未带表头总金额时为 None，不限制）
+    pub fn remaining_invoice_cap(&self, invoice_id: i64) -> Option<&BigDecimal> {
+        self.invoice_caps.get(&invoice_id)
+    }
+
+    /// 将发票移出候选（ConsumeOnce 策略），之后不会再被选中
+    pub fn retire_invoice(&mut self, invoice_id: i64) {
+        self.retired_invoices.insert(invoice_id);
+    }
+
+    /// 恢复暂时移出候选的发票（ConsumeOnce 策略下没有可整张消费的发票时回退使用），重建堆后重新参与评分
+    pub fn reinstate_invoice(&mut self, invoice_id: i64) {
+        self.retired_invoices.remove(&invoice_id);
+    }
+
+    /// 发票上仍有需求的SKU明细能否在一次选中内全部消费（各SKU可用明细金额之和不超过剩余需求）
+    pub fn is_fully_consumable(&self, invoice_id: i64, requirements: &MatchingRequirements) -> bool {
+        let mut available: HashMap<&str, BigDecimal> = HashMap::new();
+        for item in self.invoices.get(&invoice_id).into_iter().flatten() {
+            if item.remaining_amount > BigDecimal::from(0) {
+                *available.entry(item.product_code.as_str()).or_insert_with(|| BigDecimal::from(0)) += &item.remaining_amount;
+            }
+        }
+        available
+            .into_iter()
+            .all(|(sku, amount)| requirements.get_remaining(sku).is_none_or(|required| amount <= *required))
+    }
+
     /// 获取发票当前可用的明细（remaining > 0）
     pub fn get_available_items(&self, invoice_id: i64) -> Vec<InvoiceItemState> {
         self.invoices
@@ -469,6 +927,24 @@ impl InvoiceScoringContext {
         self.used_invoices.len()
     }
 
+    /// 各已使用发票被消费的SKU数（明细剩余金额低于原始金额即计入，回滚归还后不再计入）
+    pub fn sku_usage_counts(&self) -> HashMap<i64, usize> {
+        self.used_invoices
+            .iter()
+            .map(|invoice_id| {
+                let skus: HashSet<&str> = self
+                    .invoices
+                    .get(invoice_id)
+                    .into_iter()
+                    .flatten()
+                    .filter(|item| item.remaining_amount < item.original_amount)
+                    .map(|item| item.product_code.as_str())
+                    .collect();
+                (*invoice_id, skus.len())
+            })
+            .collect()
+    }
+
     /// 获取总候选发票数量
     pub fn total_count(&self) -> usize {
         self.invoices.len()
@@ -481,14 +957,387 @@ impl Default for InvoiceScoringContext {
     }
 }
 
-/// 匹配统计信息
+/// 候选明细被排除的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RejectReason {
+    /// 币种与单据明细不一致
+    CurrencyMismatch,
+    /// 单价偏离单据明细单价超出容差
+    UnitPriceMismatch,
+    /// 明细金额不大于 0
+    ZeroAmount,
+}
+
+impl RejectReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::CurrencyMismatch => "CurrencyMismatch",
+            Self::UnitPriceMismatch => "UnitPriceMismatch",
+            Self::ZeroAmount => "ZeroAmount",
+        }
+    }
+}
+
+/// 被过滤排除的候选明细（调试导出用）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RejectedItem {
+    pub invoice_id: i64,
+    pub item_id: i64,
+    pub product_code: String,
+    pub amount: BigDecimal,
+    pub currency: Option<String>,
+    pub reason: RejectReason,
+}
+
+impl RejectedItem {
+    pub fn new(item: &InvoiceItemDetail, reason: RejectReason) -> Self {
+        Self {
+            invoice_id: item.invoice_id,
+            item_id: item.item_id,
+            product_code: item.product_code.clone(),
+            amount: item.amount.clone(),
+            currency: item.currency.clone(),
+            reason,
+        }
+    }
+}
+
+/// 审计记录 - 每轮选中发票时的评分分解
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub iteration: usize,
+    pub invoice_id: i64,
+    pub score: i128,
+    pub amount_component: i128,
+    pub scarcity_component: i128,
+    pub flush_component: i128,
+    pub sku_count: i64,
+    /// 本轮在该发票上匹配的明细数
+    pub matched_items: usize,
+}
+
+/// 匹配结果的选票依据 - 与结果行一一对应（按发票明细 + SKU 关联）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MatchExplanation {
+    pub invoice_id: i64,
+    pub invoice_item_id: i64,
+    pub sku: String,
+    /// 发票被选中的轮次
+    pub iteration: usize,
+    /// 选中时（消费前）的发票总评分
+    pub score: i128,
+    /// 选中时该发票覆盖的需求SKU数
+    pub sku_count: i64,
+}
+
+/// 匹配统计信息
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct MatchStats {
     pub bill_id: i64,
     pub total_skus: usize,
+    /// max_skus 测试模式下被截掉的单据明细行数（未截断时为 0）
+    pub truncated_skus: usize,
     pub matched_skus: usize,
     pub invoices_used: usize,
+    /// 被多个SKU使用（复用）的发票数
+    pub reused_invoice_count: usize,
+    /// 只被一个SKU使用的发票数
+    pub single_use_invoice_count: usize,
     pub total_matched_amount: BigDecimal,
+    /// 单据总需求金额（匹配开始前）
+    pub total_required_amount: BigDecimal,
+    /// 匹配比例 = 已匹配金额 / 总需求金额（总需求为 0 时为 None）
+    pub match_ratio: Option<f64>,
     pub total_candidate_invoices: usize,
+    /// 因覆盖金额低于阈值被预过滤的发票数（回退时仍可能被使用）
+    pub prefiltered_invoices: usize,
+    /// 因SKU为空/空白被跳过的明细行数（单据明细 + 发票明细）
+    pub skipped_blank_skus: usize,
+    /// 因币种与单据明细不一致被排除的发票明细行数
+    pub currency_mismatch_items: usize,
+    /// 因单价偏离单据明细单价超出容差被排除的发票明细行数
+    pub unit_price_mismatch_items: usize,
+    /// 整SKU模式下因未能完全满足而撤销部分匹配的SKU数
+    pub rolled_back_skus: usize,
+    /// 合并结果行时被并入其他行的结果数 (仅启用 merge_rows 时统计)
+    pub merged_rows: usize,
+    /// 低于单行最小匹配金额而未输出结果行的匹配数
+    pub below_min_match_items: usize,
+    /// 在容差内超额匹配的SKU数
+    pub over_matched_skus: usize,
+    /// 超额匹配的总金额（超出需求的部分）
+    pub total_over_match_amount: BigDecimal,
+    /// 未匹配缺口总金额（各SKU剩余需求之和）
+    pub total_gap_amount: BigDecimal,
+    /// 未完全满足的SKU及其缺口金额，按SKU排序（计入 total_gap_amount）
+    pub unmatched: Vec<SkuGap>,
+    /// 低于需求下限的可忽略缺口（不计入 total_gap_amount），按SKU排序
+    pub negligible_gaps: Vec<SkuGap>,
+    /// 实际加载的候选分层数（未分层时为 1）
+    pub loaded_candidate_tiers: usize,
+    /// 历史模拟截止日期（不为空表示本次为历史模拟，仅使用该日期及之前开具的发票）
+    pub as_of: Option<chrono::NaiveDate>,
+    /// 匹配被取消（统计只反映取消前已匹配的部分，结果未导出）
+    pub cancelled: bool,
+    /// 单据已有完整匹配结果，本次未重新匹配（skip_if_matched，统计来自结果表）
+    pub skipped_existing: bool,
+    /// 实际运行的选票策略（最少发票求解无解或超时回退时为 greedy）
+    pub strategy: MatchStrategy,
+    /// 查询耗时（毫秒）: 单据、明细与候选发票查询，含分层加载
+    pub query_ms: u64,
+    /// 评分耗时（毫秒）: 构建评分上下文与贪心选择（流式导出时含逐条写入 CSV）
+    pub scoring_ms: u64,
+    /// 导出耗时（毫秒）: 结果文件 / COPY 写入及缺口、审计、排除明细文件
+    pub export_ms: u64,
+    /// 评分审计文件（仅启用 audit 时生成）
+    pub audit_file: Option<String>,
+    /// 选票依据文件（仅启用 explain 时生成）
+    pub explain_file: Option<String>,
+    /// 被排除候选明细文件（仅启用 export_rejected 时生成）
+    pub rejected_file: Option<String>,
     pub output_file: Option<String>,
+    /// 所有生成的结果文件（拆分导出时包含全部 part 文件）
+    pub output_files: Vec<String>,
+    /// 通过 COPY 直接写入数据库的结果行数（仅 write_mode=copy 且写入成功时有值）
+    pub inserted_rows: Option<u64>,
+    /// 匹配过程中的告警（如插入超时降级导出 CSV）
+    pub warnings: Vec<String>,
+}
+
+/// 批量运行清单 - 汇总一次批量匹配的全部单据结果与文件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchManifest {
+    pub batch_id: String,
+    pub created_at: String,
+    pub bill_count: usize,
+    /// 本批生成的全部结果文件
+    pub output_files: Vec<String>,
+    /// 各单据匹配统计（含缺口与文件路径）
+    pub bills: Vec<MatchStats>,
+}
+
+impl BatchManifest {
+    pub fn new(batch_id: String, bills: Vec<MatchStats>) -> Self {
+        let output_files = bills
+            .iter()
+            .flat_map(|stats| stats.output_files.iter().cloned())
+            .collect();
+        Self {
+            batch_id,
+            created_at: chrono::Utc::now().to_rfc3339(),
+            bill_count: bills.len(),
+            output_files,
+            bills,
+        }
+    }
+}
+
+/// 批量模拟报告 - 只在内存中匹配，汇总整批的发票消耗与缺口（不写结果表、不导出文件）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimulationReport {
+    pub bill_count: usize,
+    /// 整批使用的不同发票数（多个单据使用同一发票只计一次）
+    pub distinct_invoices_used: usize,
+    pub total_matched_amount: BigDecimal,
+    pub total_required_amount: BigDecimal,
+    /// 整批匹配比例 = 已匹配金额 / 总需求金额（总需求为 0 时为 None）
+    pub match_ratio: Option<f64>,
+    pub total_gap_amount: BigDecimal,
+    /// 各单据匹配统计（含未满足SKU缺口），按请求顺序
+    pub bills: Vec<MatchStats>,
+}
+
+impl SimulationReport {
+    pub fn new(bills: Vec<MatchStats>, distinct_invoices_used: usize) -> Self {
+        let mut total_matched_amount = BigDecimal::zero();
+        let mut total_required_amount = BigDecimal::zero();
+        let mut total_gap_amount = BigDecimal::zero();
+        for bill in &bills {
+            total_matched_amount += &bill.total_matched_amount;
+            total_required_amount += &bill.total_required_amount;
+            total_gap_amount += &bill.total_gap_amount;
+        }
+        Self {
+            bill_count: bills.len(),
+            distinct_invoices_used,
+            match_ratio: MatchStats::compute_ratio(&total_matched_amount, &total_required_amount),
+            total_matched_amount,
+            total_required_amount,
+            total_gap_amount,
+            bills,
+        }
+    }
+}
+
+impl MatchStats {
+    /// 计算匹配比例，总需求为 0 时返回 None
+    pub fn compute_ratio(matched: &BigDecimal, required: &BigDecimal) -> Option<f64> {
+        if *required <= BigDecimal::from(0) {
+            return None;
+        }
+        (matched / required).to_f64()
+    }
+
+    /// 按每张已用发票使用的SKU数统计: (被多个SKU复用的发票数, 只被一个SKU使用的发票数)
+    pub fn reuse_counts(sku_counts: impl IntoIterator<Item = usize>) -> (usize, usize) {
+        sku_counts
+            .into_iter()
+            .filter(|&count| count > 0)
+            .fold((0, 0), |(reused, single), count| if count > 1 { (reused + 1, single) } else { (reused, single + 1) })
+    }
+}
+
+/// 金额乘以 scale 后向零取整为 i128，超出范围时返回 None
+///
+/// BigDecimal::to_i128 经由 to_i64 转换，超过 i64 范围即返回 None，因此先取整再经 BigInt 转换。
+fn scaled_to_i128(amount: &BigDecimal, scale: i64) -> Option<i128> {
+    let (digits, _) = (amount * BigDecimal::from(scale)).with_scale(0).into_bigint_and_exponent();
+    digits.to_i128()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::MatchBillItem1201;
+    use std::str::FromStr;
+
+    fn amount(value: &str) -> BigDecimal {
+        BigDecimal::from_str(value).unwrap()
+    }
+
+    fn invoice_item(invoice_id: i64, item_id: i64, sku: &str, value: &str) -> InvoiceItemDetail {
+        InvoiceItemDetail {
+            invoice_id,
+            item_id,
+            product_code: sku.to_string(),
+            quantity: BigDecimal::from(1),
+            amount: amount(value),
+            unit_price: None,
+            currency: None,
+            issue_time: None,
+            invoice_total: None,
+        }
+    }
+
+    fn requirements(skus: &[(&str, &str)]) -> MatchingRequirements {
+        let bill_items: Vec<MatchBillItem1201> = skus
+            .iter()
+            .enumerate()
+            .map(|(i, (sku, value))| MatchBillItem1201 {
+                fid: 1,
+                fentryid: i as i64 + 1,
+                fspbm: sku.to_string(),
+                famount: amount(value),
+                fnum: None,
+                funitprice: None,
+                fcurrency: None,
+            })
+            .collect();
+        MatchingRequirements::from_bill_items(&bill_items, ZeroAmountPolicy::default()).unwrap()
+    }
+
+    #[test]
+    fn finer_score_scale_breaks_cent_level_ties() {
+        let reqs = requirements(&[("A", "100")]);
+        let items = vec![invoice_item(1, 11, "A", "50.0001"), invoice_item(2, 21, "A", "50.0049")];
+
+        // 默认按分计: 两张发票同分
+        let context = InvoiceScoringContext::from_items(items.clone());
+        assert_eq!(context.calculate_score_int(1, &reqs), context.calculate_score_int(2, &reqs));
+
+        // 万分位: 第四位小数区分两张发票
+        let mut context = InvoiceScoringContext::from_items(items);
+        context.set_score_scale(10000);
+        context.init_heap(&reqs);
+        assert!(context.calculate_score_int(2, &reqs).total() > context.calculate_score_int(1, &reqs).total());
+        assert_eq!(context.find_best_invoice_lazy(&reqs), Some(2));
+    }
+
+    #[test]
+    fn score_components_sum_to_total() {
+        let reqs = requirements(&[("A", "100"), ("B", "50")]);
+        let context = InvoiceScoringContext::from_items(vec![
+            // 子集红冲: 清空发票但需求未满
+            invoice_item(1, 11, "A", "30"),
+            // 完美红冲: 发票与需求完全相等
+            invoice_item(2, 21, "A", "100"),
+            invoice_item(2, 22, "B", "50"),
+            // 发票金额超过需求，不能整单红冲
+            invoice_item(3, 31, "A", "200"),
+        ]);
+
+        // A 出现在3张发票中 (1000/3 = 333)，B 仅1张 (1000/1)，默认 score_scale = 100
+        let subset = context.score_breakdown(1, &reqs);
+        assert_eq!((subset.amount_component, subset.scarcity_component, subset.flush_component), (3000, 33300, 7260));
+        let perfect = context.score_breakdown(2, &reqs);
+        assert_eq!((perfect.amount_component, perfect.scarcity_component, perfect.flush_component), (15000, 133300, 50_000_000));
+        let partial = context.score_breakdown(3, &reqs);
+        assert_eq!((partial.amount_component, partial.scarcity_component, partial.flush_component), (10000, 33300, 0));
+
+        for breakdown in [subset, perfect, partial] {
+            assert_eq!(
+                breakdown.total(),
+                breakdown.amount_component + breakdown.scarcity_component + breakdown.flush_component
+            );
+        }
+        assert_eq!(partial.leftover, 10000);
+    }
+
+    #[test]
+    fn blank_skus_are_counted_in_requirements_and_context() {
+        let reqs = requirements(&[("A", "100"), ("", "10"), ("   ", "20"), ("\u{3000}", "30")]);
+        assert_eq!(reqs.skipped_blank_skus(), 3);
+        assert_eq!(reqs.remaining_sku_count(), 1);
+        assert_eq!(reqs.total_remaining_amount(), amount("100"));
+
+        let context = InvoiceScoringContext::from_items(vec![
+            invoice_item(1, 11, "A", "100"),
+            invoice_item(1, 12, " ", "50"),
+            invoice_item(2, 21, "", "50"),
+        ]);
+        assert_eq!(context.skipped_blank_skus(), 2);
+        assert_eq!(context.total_count(), 1);
+    }
+
+    #[test]
+    fn amounts_beyond_i64_keep_invoice_order() {
+        // 按分计后超过 i64::MAX (约 9.2e18)，i64 累加会回绕成负数
+        let reqs = requirements(&[("A", "200000000000000000")]);
+        let mut context = InvoiceScoringContext::from_items(vec![
+            invoice_item(1, 11, "A", "100000000000000000"),
+            invoice_item(2, 21, "A", "150000000000000000"),
+        ]);
+        context.init_heap(&reqs);
+
+        let larger = context.score_breakdown(2, &reqs);
+        assert_eq!(larger.amount_component, 15_000_000_000_000_000_000);
+        assert!(larger.total() > context.score_breakdown(1, &reqs).total());
+        assert_eq!(context.find_best_invoice_lazy(&reqs), Some(2));
+    }
+
+    #[test]
+    fn sub_cent_amounts_order_invoices_at_finer_scale() {
+        let reqs = requirements(&[("A", "1")]);
+        let items = vec![invoice_item(1, 11, "A", "0.0012"), invoice_item(2, 21, "A", "0.0031")];
+
+        // 按分计两者都截断为 0
+        let context = InvoiceScoringContext::from_items(items.clone());
+        assert_eq!(context.score_breakdown(1, &reqs).amount_component, 0);
+        assert_eq!(context.score_breakdown(2, &reqs).amount_component, 0);
+
+        let mut context = InvoiceScoringContext::from_items(items);
+        context.set_score_scale(10000);
+        context.init_heap(&reqs);
+        assert_eq!(context.score_breakdown(1, &reqs).amount_component, 12);
+        assert_eq!(context.score_breakdown(2, &reqs).amount_component, 31);
+        assert_eq!(context.find_best_invoice_lazy(&reqs), Some(2));
+    }
+
+    #[test]
+    fn amount_beyond_i128_saturates_instead_of_dropping() {
+        let reqs = requirements(&[("A", "1e40")]);
+        let context = InvoiceScoringContext::from_items(vec![invoice_item(1, 11, "A", "1e40")]);
+
+        assert_eq!(context.score_breakdown(1, &reqs).amount_component, i128::MAX);
+    }
 }
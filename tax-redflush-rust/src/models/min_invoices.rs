@@ -0,0 +1,386 @@
+use crate::models::{normalize_product_code, InvoiceItemDetail, MatchingRequirements};
+use bigdecimal::{BigDecimal, ToPrimitive};
+use std::collections::HashMap;
+use std::time::Instant;
+
+/// 最少发票求解结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MinInvoicesOutcome {
+    /// 满足全部需求的最少发票组合
+    Optimal(Vec<i64>),
+    /// 全部候选发票合计仍不能满足需求
+    Infeasible,
+    /// 超出时间预算，未能证明最优: 返回搜索到的最优可行解（至少为贪心初始解）
+    TimedOut(Vec<i64>),
+}
+
+/// 每搜索多少个节点检查一次时间预算
+const DEADLINE_CHECK_NODES: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Decision {
+    Open,
+    Chosen,
+    Excluded,
+}
+
+/// 求解满足全部SKU需求金额的最少发票组合（分支定界）
+///
+/// 需求扣除金额容差后按 scale 缩放为整数: 需求向上取整，发票可用金额向下取整，保证解在原金额上同样可行。
+/// 每个节点选候选发票最少的未满足SKU分支，依次尝试选入其中一张发票，
+/// 前面尝试过的发票在后续分支中排除，避免重复搜索同一组合。
+/// 下界取各SKU用剩余最大额发票补足所需的最少张数，已选数加下界不小于当前最优解时剪枝。
+/// 只按金额求解，双重约束与金额规整由后续分配处理。
+pub fn solve_min_invoices(
+    items: &[InvoiceItemDetail],
+    requirements: &MatchingRequirements,
+    scale: i64,
+    deadline: Instant,
+) -> MinInvoicesOutcome {
+    let mut skus: Vec<String> = requirements.get_required_skus();
+    skus.sort();
+    let sku_index: HashMap<&str, usize> = skus.iter().enumerate().map(|(j, sku)| (sku.as_str(), j)).collect();
+    let required: Vec<i128> = skus
+        .iter()
+        .map(|sku| {
+            requirements
+                .get_remaining(sku)
+                .map_or(0, |amount| scale_amount(&(amount - requirements.tolerance()), scale, true))
+        })
+        .collect();
+
+    // 汇总每张发票各SKU的可用金额
+    let mut per_invoice: HashMap<i64, HashMap<usize, BigDecimal>> = HashMap::new();
+    for item in items {
+        let Some(&j) = sku_index.get(normalize_product_code(&item.product_code).as_str()) else {
+            continue;
+        };
+        *per_invoice
+            .entry(item.invoice_id)
+            .or_default()
+            .entry(j)
+            .or_insert_with(|| BigDecimal::from(0)) += &item.amount;
+    }
+    let mut invoice_ids: Vec<i64> = per_invoice.keys().copied().collect();
+    invoice_ids.sort_unstable();
+
+    let covers: Vec<Vec<(usize, i128)>> = invoice_ids
+        .iter()
+        .map(|invoice_id| {
+            let mut cover: Vec<(usize, i128)> = per_invoice[invoice_id]
+                .iter()
+                .map(|(&j, amount)| (j, scale_amount(amount, scale, false).min(required[j])))
+                .filter(|&(_, amount)| amount > 0)
+                .collect();
+            cover.sort_unstable();
+            cover
+        })
+        .collect();
+
+    let mut solver = Solver::new(invoice_ids, covers, required, deadline);
+    solver.solve()
+}
+
+/// 金额按 scale 缩放为整数，ceil 为 true 时向上取整，否则向下取整；超出范围时按上限计
+fn scale_amount(amount: &BigDecimal, scale: i64, ceil: bool) -> i128 {
+    let scaled = amount * BigDecimal::from(scale);
+    let truncated = scaled.with_scale(0);
+    let value = truncated.to_i128().unwrap_or(i128::MAX);
+    if ceil && truncated < scaled {
+        value.saturating_add(1)
+    } else {
+        value
+    }
+}
+
+struct Solver {
+    invoice_ids: Vec<i64>,
+    /// 每张发票对各SKU的可用金额 (SKU下标, 金额)，不超过该SKU需求
+    covers: Vec<Vec<(usize, i128)>>,
+    /// 每个SKU的候选 (可用金额, 发票下标)，按金额降序
+    sku_candidates: Vec<Vec<(i128, usize)>>,
+    /// 各SKU剩余需求（不大于 0 表示已满足）
+    remaining: Vec<i128>,
+    /// 各SKU未决发票的可用金额合计
+    available: Vec<i128>,
+    decisions: Vec<Decision>,
+    chosen: Vec<usize>,
+    best: Option<Vec<usize>>,
+    deadline: Instant,
+    nodes: usize,
+    timed_out: bool,
+}
+
+impl Solver {
+    fn new(invoice_ids: Vec<i64>, covers: Vec<Vec<(usize, i128)>>, required: Vec<i128>, deadline: Instant) -> Self {
+        let mut sku_candidates: Vec<Vec<(i128, usize)>> = vec![Vec::new(); required.len()];
+        let mut available = vec![0i128; required.len()];
+        for (i, cover) in covers.iter().enumerate() {
+            for &(j, amount) in cover {
+                sku_candidates[j].push((amount, i));
+                available[j] = available[j].saturating_add(amount);
+            }
+        }
+        for candidates in &mut sku_candidates {
+            candidates.sort_unstable_by(|a, b| b.cmp(a));
+        }
+        let decisions = vec![Decision::Open; invoice_ids.len()];
+        Self {
+            invoice_ids,
+            covers,
+            sku_candidates,
+            remaining: required,
+            available,
+            decisions,
+            chosen: Vec::new(),
+            best: None,
+            deadline,
+            nodes: 0,
+            timed_out: false,
+        }
+    }
+
+    fn solve(&mut self) -> MinInvoicesOutcome {
+        if self.remaining.iter().zip(&self.available).any(|(remaining, available)| available < remaining) {
+            return MinInvoicesOutcome::Infeasible;
+        }
+        self.best = Some(self.greedy_upper_bound());
+        self.search();
+        let mut invoice_ids: Vec<i64> = self
+            .best
+            .take()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|i| self.invoice_ids[i])
+            .collect();
+        invoice_ids.sort_unstable();
+        if self.timed_out {
+            MinInvoicesOutcome::TimedOut(invoice_ids)
+        } else {
+            MinInvoicesOutcome::Optimal(invoice_ids)
+        }
+    }
+
+    /// 贪心解作为初始上界: 每次选补足剩余需求最多的发票
+    fn greedy_upper_bound(&self) -> Vec<usize> {
+        let mut remaining = self.remaining.clone();
+        let mut picked = vec![false; self.invoice_ids.len()];
+        let mut solution = Vec::new();
+        while remaining.iter().any(|&r| r > 0) {
+            let best = (0..self.covers.len())
+                .filter(|&i| !picked[i])
+                .map(|i| {
+                    let gain: i128 = self.covers[i].iter().map(|&(j, amount)| amount.min(remaining[j].max(0))).sum();
+                    (gain, i)
+                })
+                .max();
+            let Some((_, i)) = best.filter(|&(gain, _)| gain > 0) else {
+                break;
+            };
+            picked[i] = true;
+            solution.push(i);
+            for &(j, amount) in &self.covers[i] {
+                remaining[j] -= amount;
+            }
+        }
+        solution
+    }
+
+    fn search(&mut self) {
+        self.nodes += 1;
+        // 首个节点即检查，预算在贪心初始解后已耗尽时直接返回该解
+        if self.nodes % DEADLINE_CHECK_NODES == 1 && Instant::now() >= self.deadline {
+            self.timed_out = true;
+        }
+        if self.timed_out {
+            return;
+        }
+
+        let best_len = self.best.as_ref().map_or(usize::MAX, Vec::len);
+
+        // 下界剪枝，同时选出候选发票最少的未满足SKU
+        let mut lower_bound = 0;
+        let mut branch_sku: Option<(usize, usize)> = None;
+        for (j, &remaining) in self.remaining.iter().enumerate() {
+            if remaining <= 0 {
+                continue;
+            }
+            let Some(needed) = self.min_invoices_for(j) else {
+                return;
+            };
+            lower_bound = lower_bound.max(needed);
+            let open = self.sku_candidates[j].iter().filter(|&&(_, i)| self.decisions[i] == Decision::Open).count();
+            if branch_sku.is_none_or(|(_, fewest)| open < fewest) {
+                branch_sku = Some((j, open));
+            }
+        }
+
+        let Some((j, _)) = branch_sku else {
+            if self.chosen.len() < best_len {
+                self.best = Some(self.chosen.clone());
+            }
+            return;
+        };
+        if self.chosen.len() + lower_bound >= best_len {
+            return;
+        }
+
+        let candidates: Vec<usize> = self.sku_candidates[j]
+            .iter()
+            .filter(|&&(_, i)| self.decisions[i] == Decision::Open)
+            .map(|&(_, i)| i)
+            .collect();
+        let mut excluded = Vec::new();
+        for i in candidates {
+            self.choose(i);
+            self.search();
+            self.unchoose(i);
+            if self.timed_out {
+                break;
+            }
+            // 后续分支不再考虑该发票
+            self.exclude(i);
+            excluded.push(i);
+            if self.available[j] < self.remaining[j] {
+                break;
+            }
+        }
+        for i in excluded {
+            self.restore(i);
+        }
+    }
+
+    /// 用未决发票中金额最大的若干张补足SKU剩余需求所需的最少张数，补不足时返回 None
+    fn min_invoices_for(&self, j: usize) -> Option<usize> {
+        let mut covered = 0i128;
+        let mut count = 0;
+        for &(amount, i) in &self.sku_candidates[j] {
+            if self.decisions[i] != Decision::Open {
+                continue;
+            }
+            covered = covered.saturating_add(amount);
+            count += 1;
+            if covered >= self.remaining[j] {
+                return Some(count);
+            }
+        }
+        None
+    }
+
+    fn choose(&mut self, i: usize) {
+        self.decisions[i] = Decision::Chosen;
+        self.chosen.push(i);
+        for &(j, amount) in &self.covers[i] {
+            self.remaining[j] -= amount;
+            self.available[j] -= amount;
+        }
+    }
+
+    fn unchoose(&mut self, i: usize) {
+        self.decisions[i] = Decision::Open;
+        self.chosen.pop();
+        for &(j, amount) in &self.covers[i] {
+            self.remaining[j] += amount;
+            self.available[j] += amount;
+        }
+    }
+
+    fn exclude(&mut self, i: usize) {
+        self.decisions[i] = Decision::Excluded;
+        for &(j, amount) in &self.covers[i] {
+            self.available[j] -= amount;
+        }
+    }
+
+    fn restore(&mut self, i: usize) {
+        self.decisions[i] = Decision::Open;
+        for &(j, amount) in &self.covers[i] {
+            self.available[j] += amount;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ZeroAmountPolicy;
+    use crate::models::MatchBillItem1201;
+    use std::time::Duration;
+
+    fn requirements(demands: &[(&str, i64)]) -> MatchingRequirements {
+        let bill_items: Vec<MatchBillItem1201> = demands
+            .iter()
+            .enumerate()
+            .map(|(entry, &(sku, amount))| MatchBillItem1201 {
+                fid: 1,
+                fentryid: entry as i64,
+                fspbm: sku.to_string(),
+                famount: BigDecimal::from(amount),
+                fnum: None,
+                funitprice: None,
+                fcurrency: None,
+            })
+            .collect();
+        MatchingRequirements::from_bill_items(&bill_items, ZeroAmountPolicy::default()).unwrap()
+    }
+
+    fn item(invoice_id: i64, sku: &str, amount: i64) -> InvoiceItemDetail {
+        InvoiceItemDetail {
+            invoice_id,
+            item_id: invoice_id * 10,
+            product_code: sku.to_string(),
+            quantity: BigDecimal::from(1),
+            amount: BigDecimal::from(amount),
+            unit_price: None,
+            currency: None,
+            issue_time: None,
+            invoice_total: None,
+        }
+    }
+
+    /// 贪心先选覆盖最多的发票1（A、B 各 60），之后还要发票2、3 补足，共 3 张；最优解只需发票2、3
+    fn greedy_trap() -> (Vec<InvoiceItemDetail>, MatchingRequirements) {
+        let items = vec![item(1, "A", 60), item(1, "B", 60), item(2, "A", 100), item(3, "B", 100)];
+        (items, requirements(&[("A", 100), ("B", 100)]))
+    }
+
+    fn far_deadline() -> Instant {
+        Instant::now() + Duration::from_secs(5)
+    }
+
+    #[test]
+    fn solver_finds_optimum_where_greedy_does_not() {
+        let (items, reqs) = greedy_trap();
+
+        assert_eq!(solve_min_invoices(&items, &reqs, 100, far_deadline()), MinInvoicesOutcome::Optimal(vec![2, 3]));
+    }
+
+    #[test]
+    fn insufficient_candidates_are_infeasible() {
+        let items = vec![item(1, "A", 60), item(2, "A", 30), item(3, "B", 100)];
+        let reqs = requirements(&[("A", 100), ("B", 50)]);
+
+        assert_eq!(solve_min_invoices(&items, &reqs, 100, far_deadline()), MinInvoicesOutcome::Infeasible);
+    }
+
+    #[test]
+    fn timed_out_search_keeps_feasible_incumbent() {
+        let (items, reqs) = greedy_trap();
+        let deadline = Instant::now();
+
+        let MinInvoicesOutcome::TimedOut(invoice_ids) = solve_min_invoices(&items, &reqs, 100, deadline) else {
+            panic!("预算耗尽时应返回 TimedOut");
+        };
+
+        // 预算在贪心初始解后即耗尽: 返回贪心解（3 张）而非要求重新求解，且组合覆盖全部需求
+        assert_eq!(invoice_ids, vec![1, 2, 3]);
+        for (sku, required) in [("A", 100), ("B", 100)] {
+            let covered: i64 = items
+                .iter()
+                .filter(|item| item.product_code == sku && invoice_ids.contains(&item.invoice_id))
+                .map(|item| item.amount.to_i64().unwrap())
+                .sum();
+            assert!(covered >= required, "{} 覆盖 {} < {}", sku, covered, required);
+        }
+    }
+}
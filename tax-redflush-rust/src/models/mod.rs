@@ -1,12 +1,21 @@
 pub mod bill;
+pub mod compare;
 pub mod invoice;
 pub mod invoice_centric;
+pub mod min_invoices;
+pub mod product_code;
 pub mod result;
+pub mod shared_context;
 
 pub use bill::{MatchBill1201, MatchBillItem1201, TempSummary};
+pub use compare::{AllocationChange, InvoiceOverlap, MatchAllocation, ResultDiff};
 pub use invoice::{CandidateStat, MatchedInvoiceItem};
 pub use invoice_centric::{
-    InvoiceCoverage, InvoiceItemDetail, InvoiceScoringContext, InvoiceWithItems,
-    MatchStats, MatchingRequirements,
+    AuditEntry, BatchManifest, InvoiceCoverage, InvoiceItemDetail, InvoiceScoringContext, InvoiceWithItems,
+    MatchExplanation, MatchStats, MatchingRequirements, RejectReason, RejectedItem, ScoreBreakdown,
+    SimulationReport,
 };
-pub use result::MatchResult1201;
+pub use min_invoices::{solve_min_invoices, MinInvoicesOutcome};
+pub use product_code::normalize_product_code;
+pub use result::{BillMatchResults, MatchResult1201, SkuGap};
+pub use shared_context::SharedScoringContext;
@@ -0,0 +1,29 @@
+/// 商品编码归一化，所有以商品编码为键的地方（需求、倒排索引、单据明细查找）统一使用
+///
+/// 去除首尾空白（含全角空格），全角 ASCII 字符转半角，英文字母转大写。
+/// 数据库查询仍按原值精确匹配，见 `MatchingRequirements::query_skus`。
+pub fn normalize_product_code(code: &str) -> String {
+    code.trim()
+        .chars()
+        .map(|c| match c {
+            '\u{3000}' => ' ',
+            '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+            _ => c,
+        })
+        .collect::<String>()
+        .trim()
+        .to_ascii_uppercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trims_folds_width_and_case() {
+        assert_eq!(normalize_product_code(" ABC "), "ABC");
+        assert_eq!(normalize_product_code("\u{3000}abc\t"), "ABC");
+        assert_eq!(normalize_product_code("ＡＢＣ１２３"), "ABC123");
+        assert_eq!(normalize_product_code("1090000000000000000"), "1090000000000000000");
+    }
+}
@@ -1,6 +1,7 @@
 use bigdecimal::BigDecimal;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 /// 匹配结果表 (MatchResult1201)
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -20,4 +21,21 @@ pub struct MatchResult1201 {
     pub finvoiceunitprice: Option<BigDecimal>,
     pub finvoiceqty: Option<BigDecimal>,
     pub fmatchtime: DateTime<Utc>,
+    /// 注解器写入的扩展字段（不入库，导出时追加为额外列）
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub extra: HashMap<String, String>,
+}
+
+/// 单个单据的匹配结果行
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BillMatchResults {
+    pub bill_id: i64,
+    pub results: Vec<MatchResult1201>,
+}
+
+/// 单个SKU的匹配缺口（未满足金额）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkuGap {
+    pub sku: String,
+    pub gap_amount: BigDecimal,
 }
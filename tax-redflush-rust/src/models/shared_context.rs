@@ -0,0 +1,137 @@
+use super::invoice_centric::{InvoiceItemState, InvoiceScoringContext, MatchingRequirements};
+use bigdecimal::BigDecimal;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// 线程安全的评分上下文 - 供多个单据任务并发共享同一候选池
+///
+/// 单线程路径继续直接使用 `InvoiceScoringContext`，不引入锁开销。
+/// 需要跨多步保持一致（如 选择 -> 消费）时使用 `with_lock` 在同一把锁内完成。
+#[derive(Debug, Clone)]
+pub struct SharedScoringContext {
+    inner: Arc<Mutex<InvoiceScoringContext>>,
+}
+
+impl SharedScoringContext {
+    pub fn new(context: InvoiceScoringContext) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(context)),
+        }
+    }
+
+    /// 获取锁（锁被 panic 污染时仍返回内部数据，剩余金额只会单调递减）
+    fn lock(&self) -> MutexGuard<'_, InvoiceScoringContext> {
+        self.inner.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// 在同一把锁内执行多步操作
+    pub fn with_lock<R>(&self, f: impl FnOnce(&mut InvoiceScoringContext) -> R) -> R {
+        let mut guard = self.lock();
+        f(&mut guard)
+    }
+
+    /// 初始化堆
+    pub fn init_heap(&self, requirements: &MatchingRequirements) {
+        self.lock().init_heap(requirements);
+    }
+
+    /// 查找最优发票
+    pub fn find_best_invoice_lazy(&self, requirements: &MatchingRequirements) -> Option<i64> {
+        self.lock().find_best_invoice_lazy(requirements)
+    }
+
+    /// 消费明细金额
+    pub fn consume_item(&self, invoice_id: i64, product_code: &str, amount: &BigDecimal) -> Option<InvoiceItemState> {
+        self.lock().consume_item(invoice_id, product_code, amount)
+    }
+
+    /// 获取发票当前可用的明细
+    pub fn get_available_items(&self, invoice_id: i64) -> Vec<InvoiceItemState> {
+        self.lock().get_available_items(invoice_id)
+    }
+
+    /// 获取已使用的发票数量
+    pub fn used_count(&self) -> usize {
+        self.lock().used_count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ZeroAmountPolicy;
+    use crate::models::{InvoiceItemDetail, MatchBillItem1201};
+
+    fn invoice_item(invoice_id: i64, value: i64) -> InvoiceItemDetail {
+        InvoiceItemDetail {
+            invoice_id,
+            item_id: invoice_id * 10,
+            product_code: "A".to_string(),
+            quantity: BigDecimal::from(1),
+            amount: BigDecimal::from(value),
+            unit_price: None,
+            currency: None,
+            issue_time: None,
+            invoice_total: None,
+        }
+    }
+
+    fn requirements(value: i64) -> MatchingRequirements {
+        let bill_item = MatchBillItem1201 {
+            fid: 1,
+            fentryid: 1,
+            fspbm: "A".to_string(),
+            famount: BigDecimal::from(value),
+            fnum: None,
+            funitprice: None,
+            fcurrency: None,
+        };
+        MatchingRequirements::from_bill_items(&[bill_item], ZeroAmountPolicy::default()).unwrap()
+    }
+
+    /// 单个单据任务: 每轮在同一把锁内选票并消费，直到需求满足或候选耗尽
+    fn consume_until_satisfied(shared: &SharedScoringContext, demand: i64) -> BigDecimal {
+        let mut reqs = requirements(demand);
+        let mut consumed = BigDecimal::from(0);
+        while !reqs.is_satisfied() {
+            let round = shared.with_lock(|context| {
+                context.init_heap(&reqs);
+                let invoice_id = context.find_best_invoice_lazy(&reqs)?;
+                let mut taken = BigDecimal::from(0);
+                for item in context.get_available_items(invoice_id) {
+                    let Some(required) = reqs.get_remaining(&item.product_code).cloned() else {
+                        continue;
+                    };
+                    let amount = if item.remaining_amount < required { item.remaining_amount.clone() } else { required };
+                    context.consume_item(invoice_id, &item.product_code, &amount);
+                    reqs.reduce(&item.product_code, &amount);
+                    taken += amount;
+                }
+                Some(taken)
+            });
+            match round {
+                Some(taken) => consumed += taken,
+                None => break,
+            }
+        }
+        consumed
+    }
+
+    #[test]
+    fn concurrent_consumption_never_exceeds_availability() {
+        // 5 张发票共 300，8 个任务各需求 80，总需求远超可用额度
+        let shared = SharedScoringContext::new(InvoiceScoringContext::from_items(
+            (1..=5).map(|invoice_id| invoice_item(invoice_id, 60)).collect(),
+        ));
+
+        let consumed: Vec<BigDecimal> = std::thread::scope(|scope| {
+            let tasks: Vec<_> = (0..8).map(|_| scope.spawn(|| consume_until_satisfied(&shared, 80))).collect();
+            tasks.into_iter().map(|task| task.join().unwrap()).collect()
+        });
+
+        let total: BigDecimal = consumed.iter().sum();
+        assert_eq!(total, BigDecimal::from(300));
+        assert!(consumed.iter().all(|amount| *amount <= BigDecimal::from(80)));
+        assert_eq!(shared.used_count(), 5);
+        assert!((1..=5).all(|invoice_id| shared.get_available_items(invoice_id).is_empty()));
+    }
+}
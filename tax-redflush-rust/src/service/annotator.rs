@@ -0,0 +1,106 @@
+use crate::models::MatchResult1201;
+
+/// 匹配结果注解器 - 导出前为每条结果补充派生字段
+///
+/// 注解写入 `MatchResult1201::extra`，按 `columns()` 的顺序追加为 CSV 列，
+/// JSON 输出中作为 `extra` 对象。集成方可据此生成外部单据号等字段，无需修改匹配逻辑。
+pub trait ResultAnnotator: Send + Sync {
+    /// 追加到 CSV 的列名（顺序即输出顺序）
+    fn columns(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// 为单条匹配结果写入注解
+    fn annotate(&self, result: &mut MatchResult1201);
+}
+
+/// 默认注解器: 不做任何处理
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoopAnnotator;
+
+impl ResultAnnotator for NoopAnnotator {
+    fn annotate(&self, _result: &mut MatchResult1201) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::queries::{self, CsvOptions};
+    use bigdecimal::BigDecimal;
+    use chrono::Utc;
+    use std::collections::HashMap;
+
+    /// 以 单据ID-发票明细ID 作为外部单据号
+    struct ExternalRefAnnotator;
+
+    impl ResultAnnotator for ExternalRefAnnotator {
+        fn columns(&self) -> Vec<String> {
+            vec!["external_ref".to_string()]
+        }
+
+        fn annotate(&self, result: &mut MatchResult1201) {
+            let reference = format!("{}-{}", result.fbillid, result.finvoiceitemid);
+            result.extra.insert("external_ref".to_string(), reference);
+        }
+    }
+
+    fn sample_result(item_id: i64) -> MatchResult1201 {
+        MatchResult1201 {
+            fbillid: 241,
+            fbuyertaxno: "BUYER".to_string(),
+            fsalertaxno: "SELLER".to_string(),
+            fspbm: "A".to_string(),
+            finvoiceid: 1,
+            finvoiceitemid: item_id,
+            fnum: BigDecimal::from(1),
+            fbillamount: BigDecimal::from(100),
+            finvoiceamount: BigDecimal::from(50),
+            fmatchamount: BigDecimal::from(50),
+            fbillunitprice: None,
+            fbillqty: None,
+            finvoiceunitprice: None,
+            finvoiceqty: None,
+            fmatchtime: Utc::now(),
+            extra: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn annotated_field_appears_in_exports() {
+        let annotator = ExternalRefAnnotator;
+        let mut results = vec![sample_result(11), sample_result(12)];
+        results.iter_mut().for_each(|result| annotator.annotate(result));
+
+        let dir = std::env::temp_dir().join(format!("redflush_test_annotator_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let csv_path = dir.join("match_results_241.csv");
+        let options = CsvOptions { extra_columns: annotator.columns(), ..CsvOptions::default() };
+        queries::export_to_csv(&results, &csv_path, &options).unwrap();
+
+        let refs: Vec<String> = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .from_path(&csv_path)
+            .unwrap()
+            .records()
+            .map(|row| row.unwrap()[15].to_string())
+            .collect();
+        assert_eq!(refs, ["241-11", "241-12"]);
+        let meta = queries::validate_csv_schema(&csv_path, queries::CSV_SCHEMA_VERSION).unwrap();
+        assert_eq!(meta.columns.last().map(String::as_str), Some("external_ref"));
+
+        let jsonl_path = dir.join("match_results_241.jsonl");
+        queries::export_to_jsonl(&results, &jsonl_path).unwrap();
+        let first_line = std::fs::read_to_string(&jsonl_path).unwrap().lines().next().unwrap().to_string();
+        let first: serde_json::Value = serde_json::from_str(&first_line).unwrap();
+        assert_eq!(first["extra"]["external_ref"], "241-11");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn noop_annotator_adds_nothing() {
+        let mut result = sample_result(11);
+        NoopAnnotator.annotate(&mut result);
+        assert!(result.extra.is_empty());
+        assert!(NoopAnnotator.columns().is_empty());
+    }
+}
@@ -0,0 +1,55 @@
+use serde::Serialize;
+use std::collections::HashSet;
+
+/// 从文本解析出的单据ID列表
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ParsedBillIds {
+    /// 有效单据ID，按首次出现顺序去重
+    pub bill_ids: Vec<i64>,
+    /// 重复出现被去除的ID个数
+    pub duplicates: usize,
+    /// 无法解析的行
+    pub invalid: Vec<InvalidBillIdLine>,
+}
+
+/// 无法解析为单据ID的行
+#[derive(Debug, Clone, Serialize)]
+pub struct InvalidBillIdLine {
+    /// 行号（从 1 开始）
+    pub line: usize,
+    pub content: String,
+    pub reason: String,
+}
+
+/// 解析单据ID文本: 每行一个ID，或单列 CSV（取第一列）
+///
+/// 空行与 `#` 开头的注释行忽略；首个非空行不是数字时视为表头跳过。
+/// 单据ID须为正整数，其余行记入 invalid，不中断解析。
+pub fn parse_bill_ids(content: &str) -> ParsedBillIds {
+    let mut parsed = ParsedBillIds::default();
+    let mut seen = HashSet::new();
+    let mut first_value = true;
+    for (index, raw) in content.lines().enumerate() {
+        let line = raw.trim().trim_start_matches('\u{feff}');
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let field = line.split(',').next().unwrap_or_default().trim().trim_matches('"').trim();
+        let is_first = std::mem::take(&mut first_value);
+        let reason = match field.parse::<i64>() {
+            Ok(id) if id > 0 => {
+                if seen.insert(id) {
+                    parsed.bill_ids.push(id);
+                } else {
+                    parsed.duplicates += 1;
+                }
+                continue;
+            }
+            Ok(_) => "单据ID须为正数".to_string(),
+            Err(_) if is_first => continue,
+            Err(e) => format!("无法解析为单据ID: {}", e),
+        };
+        parsed.invalid.push(InvalidBillIdLine { line: index + 1, content: line.to_string(), reason });
+    }
+    parsed
+}
@@ -0,0 +1,83 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
+
+/// 单据级互斥锁表 - 保证同一单据不会被并发匹配
+///
+/// 后到的调用方会等待前一次匹配完成，而不是并行写出重复结果。
+#[derive(Debug, Default)]
+pub struct BillLockRegistry {
+    locks: Mutex<HashMap<i64, Arc<AsyncMutex<()>>>>,
+}
+
+impl BillLockRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 获取单据锁，持有返回的 guard 期间其他调用方会等待
+    pub async fn lock(&self, bill_id: i64) -> OwnedMutexGuard<()> {
+        let bill_lock = {
+            let mut locks = self.locks.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            // 顺带清理无人持有的锁，避免锁表无限增长
+            locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+            locks.entry(bill_id).or_default().clone()
+        };
+
+        if bill_lock.try_lock().is_err() {
+            tracing::info!("Bill {} 正在被其他请求匹配, 等待其完成...", bill_id);
+        }
+
+        bill_lock.lock_owned().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    /// 模拟一次单据匹配: 先查已有输出再写入，持锁期间记录并发数
+    async fn match_once(registry: &BillLockRegistry, bill_id: i64, active: &AtomicUsize, peak: &AtomicUsize, output: &Mutex<Vec<i64>>) {
+        let _guard = registry.lock(bill_id).await;
+        let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+        peak.fetch_max(now, Ordering::SeqCst);
+        let already_matched = output.lock().unwrap().contains(&bill_id);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        if !already_matched {
+            output.lock().unwrap().push(bill_id);
+        }
+        active.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn same_bill_matches_are_serialized() {
+        let registry = BillLockRegistry::new();
+        let (active, peak) = (AtomicUsize::new(0), AtomicUsize::new(0));
+        let output = Mutex::new(Vec::new());
+
+        tokio::join!(
+            match_once(&registry, 212, &active, &peak, &output),
+            match_once(&registry, 212, &active, &peak, &output),
+        );
+
+        assert_eq!(peak.load(Ordering::SeqCst), 1);
+        assert_eq!(*output.lock().unwrap(), vec![212]);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn different_bills_run_concurrently() {
+        let registry = BillLockRegistry::new();
+        let (active, peak) = (AtomicUsize::new(0), AtomicUsize::new(0));
+        let output = Mutex::new(Vec::new());
+
+        tokio::join!(
+            match_once(&registry, 212, &active, &peak, &output),
+            match_once(&registry, 213, &active, &peak, &output),
+        );
+
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+        assert_eq!(output.lock().unwrap().len(), 2);
+    }
+}
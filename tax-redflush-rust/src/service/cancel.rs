@@ -0,0 +1,7 @@
+//! 协作式取消
+//!
+//! 使用 `tokio_util::sync::CancellationToken`: 停机令牌派生出请求与异步任务的令牌，
+//! 批量匹配再为每个单据派生子令牌。取消父令牌会传递到所有子令牌，匹配在下一个检查点
+//! （贪心循环每轮开始、候选明细分批查询之间）停止并返回已匹配部分的统计。
+
+pub use tokio_util::sync::CancellationToken;
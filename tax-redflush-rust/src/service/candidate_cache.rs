@@ -0,0 +1,133 @@
+use crate::models::{InvoiceItemDetail, MatchBill1201};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// 批量内按销购方税号对共享候选发票明细
+///
+/// 同一税号对的单据候选发票相同，首个单据查询后缓存候选发票ID与已查SKU的明细，
+/// 后续单据只补查缓存中没有的SKU。每个单据取走明细副本后各自构建评分上下文，
+/// 剩余金额都从原始金额开始，单据之间互不影响。
+/// 只缓存批量中出现两次以上的税号对。发票数据在批量期间不变，候选发票ID保留到批量结束；
+/// 明细占用内存较多，税号对的单据都取用过后释放。
+#[derive(Debug, Default)]
+pub struct CandidateCache {
+    pairs: Mutex<HashMap<(String, String), PairEntry>>,
+    /// 候选发票ID实际查询次数
+    invoice_id_queries: AtomicUsize,
+    /// 候选发票ID复用缓存次数
+    invoice_id_hits: AtomicUsize,
+}
+
+#[derive(Debug)]
+struct PairEntry {
+    /// 尚未取用缓存的单据数，归零后释放明细
+    pending_bills: usize,
+    candidates: Arc<tokio::sync::Mutex<PairCandidates>>,
+}
+
+/// 单个税号对已查询的候选发票明细
+#[derive(Debug, Default)]
+pub struct PairCandidates {
+    /// 候选发票ID，首个单据查询后填充
+    pub invoice_ids: Option<Vec<i64>>,
+    /// 已查询明细的商品编码（查询用写法）
+    fetched_skus: HashSet<String>,
+    items: Vec<InvoiceItemDetail>,
+}
+
+impl PairCandidates {
+    /// 缓存中尚未查询的商品编码
+    pub fn missing_skus(&self, sku_list: &[String]) -> Vec<String> {
+        sku_list.iter().filter(|sku| !self.fetched_skus.contains(*sku)).cloned().collect()
+    }
+
+    /// 记录补查的商品编码及其明细
+    pub fn extend(&mut self, skus: Vec<String>, items: Vec<InvoiceItemDetail>) {
+        self.fetched_skus.extend(skus);
+        self.items.extend(items);
+    }
+
+    /// 释放明细，保留候选发票ID
+    fn release_items(&mut self) {
+        self.fetched_skus = HashSet::new();
+        self.items = Vec::new();
+    }
+
+    /// 指定商品编码的明细副本，按发票ID、金额降序、明细ID排序（与单独查询的顺序一致）
+    pub fn items_for(&self, sku_list: &[String]) -> Vec<InvoiceItemDetail> {
+        let skus: HashSet<&str> = sku_list.iter().map(String::as_str).collect();
+        let mut items: Vec<InvoiceItemDetail> =
+            self.items.iter().filter(|item| skus.contains(item.product_code.as_str())).cloned().collect();
+        items.sort_by(|a, b| {
+            a.invoice_id
+                .cmp(&b.invoice_id)
+                .then_with(|| b.amount.cmp(&a.amount))
+                .then_with(|| a.item_id.cmp(&b.item_id))
+        });
+        items
+    }
+}
+
+impl CandidateCache {
+    /// 按批量单据的税号对建立缓存，只出现一次的税号对不缓存
+    pub fn new(bills: &[MatchBill1201]) -> Self {
+        let mut counts: HashMap<(String, String), usize> = HashMap::new();
+        for bill in bills {
+            *counts.entry((bill.fbuyertaxno.clone(), bill.fsalertaxno.clone())).or_default() += 1;
+        }
+        let pairs = counts
+            .into_iter()
+            .filter(|&(_, count)| count > 1)
+            .map(|(pair, count)| {
+                let entry = PairEntry { pending_bills: count, candidates: Arc::default() };
+                (pair, entry)
+            })
+            .collect();
+        Self { pairs: Mutex::new(pairs), ..Self::default() }
+    }
+
+    /// 记录一次候选发票ID查找，hit 表示复用了缓存
+    pub fn record_invoice_id_lookup(&self, hit: bool) {
+        let counter = if hit { &self.invoice_id_hits } else { &self.invoice_id_queries };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 候选发票ID的 (查询次数, 复用次数)
+    pub fn invoice_id_lookups(&self) -> (usize, usize) {
+        (self.invoice_id_queries.load(Ordering::Relaxed), self.invoice_id_hits.load(Ordering::Relaxed))
+    }
+
+    /// 缓存的税号对数
+    pub fn len(&self) -> usize {
+        self.pairs.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 税号对的缓存，未缓存时返回 None
+    /// 查询期间持有返回的锁，同税号对的其他单据等待后直接复用
+    pub fn get(&self, buyer_tax_no: &str, seller_tax_no: &str) -> Option<Arc<tokio::sync::Mutex<PairCandidates>>> {
+        let pairs = self.pairs.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        pairs
+            .get(&(buyer_tax_no.to_string(), seller_tax_no.to_string()))
+            .map(|entry| entry.candidates.clone())
+    }
+
+    /// 单据已取用缓存，税号对的单据都取用过后释放明细，候选发票ID保留到批量结束
+    /// 调用方需已释放 get 返回的锁
+    pub fn release(&self, buyer_tax_no: &str, seller_tax_no: &str) {
+        let mut pairs = self.pairs.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let key = (buyer_tax_no.to_string(), seller_tax_no.to_string());
+        if let Some(entry) = pairs.get_mut(&key) {
+            entry.pending_bills = entry.pending_bills.saturating_sub(1);
+            if entry.pending_bills == 0 {
+                if let Ok(mut candidates) = entry.candidates.try_lock() {
+                    candidates.release_items();
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,28 @@
+use crate::config::MatchOptions;
+use crate::models::InvoiceOverlap;
+use crate::service::{InvoiceCentricMatcher, MatcherService};
+
+/// 对同一单据分别以 dry-run 方式运行两种算法，比较所选发票的重叠情况
+/// 不写数据库、不导出文件
+pub async fn compare_invoice_overlap(
+    sku_centric: &MatcherService,
+    invoice_centric: &InvoiceCentricMatcher,
+    bill_id: i64,
+) -> Result<InvoiceOverlap, Box<dyn std::error::Error + Send + Sync>> {
+    let Some(sku_outcome) = sku_centric.match_bill(bill_id, false, false, sku_centric.config()).await? else {
+        return Err(format!("Bill {} not found", bill_id).into());
+    };
+    let sku_invoices = sku_outcome.used_invoices;
+
+    let outcome = invoice_centric
+        .compute_bill_matches(bill_id, &MatchOptions::default(), invoice_centric.config())
+        .await?;
+    let invoice_centric_invoices: Vec<i64> = outcome.results.iter().map(|r| r.finvoiceid).collect();
+
+    tracing::info!(
+        "[Compare] Bill {}: SKU-Centric 使用 {} 张发票, Invoice-Centric 使用 {} 张发票",
+        bill_id, sku_invoices.len(), invoice_centric_invoices.len()
+    );
+
+    Ok(InvoiceOverlap::from_invoice_sets(bill_id, &sku_invoices, &invoice_centric_invoices))
+}
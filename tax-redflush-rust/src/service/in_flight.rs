@@ -0,0 +1,59 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+
+/// 进行中的匹配计数 - 停机时等待匹配请求与异步任务结束
+///
+/// 每个匹配请求/异步任务持有一个 guard，guard 释放时计数减一，计数归零时唤醒等待方。
+#[derive(Debug, Default)]
+pub struct InFlightTracker {
+    active: AtomicUsize,
+    idle: Notify,
+}
+
+/// 进行中匹配的登记，释放时计数减一
+#[derive(Debug)]
+pub struct InFlightGuard {
+    tracker: Arc<InFlightTracker>,
+}
+
+impl InFlightTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 登记一个进行中的匹配，持有返回的 guard 直到匹配结束
+    pub fn enter(self: &Arc<Self>) -> InFlightGuard {
+        self.active.fetch_add(1, Ordering::SeqCst);
+        InFlightGuard { tracker: self.clone() }
+    }
+
+    /// 进行中的匹配数
+    pub fn active(&self) -> usize {
+        self.active.load(Ordering::SeqCst)
+    }
+
+    /// 等待进行中的匹配全部结束，最多等待 timeout；全部结束返回 true
+    pub async fn wait_idle(&self, timeout: Duration) -> bool {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            // 先登记等待再检查计数，避免检查后、等待前归零而错过唤醒
+            let idle = self.idle.notified();
+            if self.active() == 0 {
+                return true;
+            }
+            if tokio::time::timeout_at(deadline, idle).await.is_err() {
+                return self.active() == 0;
+            }
+        }
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        if self.tracker.active.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.tracker.idle.notify_waiters();
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use crate::models::MatchStats;
+use crate::service::{BatchProgress, CancellationToken, ProgressSnapshot};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+/// 异步匹配任务状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Done,
+    Failed,
+    /// 已取消，stats 为取消前已完成的部分
+    Cancelled,
+}
+
+impl JobStatus {
+    /// 任务是否已结束（不会再变化）
+    pub fn is_finished(self) -> bool {
+        matches!(self, JobStatus::Done | JobStatus::Failed | JobStatus::Cancelled)
+    }
+}
+
+/// 异步匹配任务
+#[derive(Debug)]
+pub struct JobState {
+    pub status: JobStatus,
+    /// 任务自己的进度计数器（不与同步批量共享）
+    pub progress: Arc<BatchProgress>,
+    /// 任务取消令牌（停机令牌的子令牌），由 `POST /api/match/jobs/:job_id/cancel` 或停机触发
+    pub cancel: CancellationToken,
+    pub stats: Option<Vec<MatchStats>>,
+    pub error: Option<String>,
+}
+
+/// 任务快照，供 `GET /api/match/jobs/:job_id` 返回
+#[derive(Debug, Clone, Serialize)]
+pub struct JobSnapshot {
+    pub job_id: Uuid,
+    pub status: JobStatus,
+    pub progress: ProgressSnapshot,
+    pub stats: Option<Vec<MatchStats>>,
+    pub error: Option<String>,
+}
+
+/// 异步匹配任务登记表
+///
+/// 任务状态仅保存在内存中，服务重启后丢失。
+#[derive(Debug, Default)]
+pub struct JobRegistry {
+    jobs: Mutex<HashMap<Uuid, JobState>>,
+    /// 停机令牌，各任务令牌均由其派生
+    shutdown: CancellationToken,
+}
+
+impl JobRegistry {
+    /// 取消 shutdown 即取消所有未结束的任务
+    pub fn new(shutdown: CancellationToken) -> Self {
+        Self { jobs: Mutex::default(), shutdown }
+    }
+
+    /// 登记新任务（Queued），返回任务ID、进度计数器与取消令牌
+    pub fn submit(&self, total: usize) -> (Uuid, Arc<BatchProgress>, CancellationToken) {
+        let job_id = Uuid::new_v4();
+        let progress = Arc::new(BatchProgress::new());
+        progress.reset(total);
+        let cancel = self.shutdown.child_token();
+
+        let state = JobState {
+            status: JobStatus::Queued,
+            progress: progress.clone(),
+            cancel: cancel.clone(),
+            stats: None,
+            error: None,
+        };
+        self.jobs.lock().unwrap().insert(job_id, state);
+        (job_id, progress, cancel)
+    }
+
+    /// 请求取消任务，返回任务当前状态；任务不存在时返回 None
+    ///
+    /// 已结束的任务不受影响。运行中的任务在下一个检查点停止。
+    pub fn cancel(&self, job_id: Uuid) -> Option<JobStatus> {
+        let jobs = self.jobs.lock().unwrap();
+        let job = jobs.get(&job_id)?;
+        if !job.status.is_finished() {
+            job.cancel.cancel();
+        }
+        Some(job.status)
+    }
+
+    /// 标记任务开始运行
+    pub fn start(&self, job_id: Uuid) {
+        if let Some(job) = self.jobs.lock().unwrap().get_mut(&job_id) {
+            job.status = JobStatus::Running;
+        }
+    }
+
+    /// 记录任务结果
+    pub fn finish(&self, job_id: Uuid, result: Result<Vec<MatchStats>, String>) {
+        if let Some(job) = self.jobs.lock().unwrap().get_mut(&job_id) {
+            match result {
+                Ok(stats) => {
+                    job.status = if job.cancel.is_cancelled() { JobStatus::Cancelled } else { JobStatus::Done };
+                    job.stats = Some(stats);
+                }
+                Err(e) => {
+                    job.status = JobStatus::Failed;
+                    job.error = Some(e);
+                }
+            }
+        }
+    }
+
+    /// 未结束（排队中或运行中）的任务数
+    pub fn active_count(&self) -> usize {
+        self.jobs.lock().unwrap().values().filter(|job| !job.status.is_finished()).count()
+    }
+
+    /// 查询任务快照，任务不存在时返回 None
+    pub fn snapshot(&self, job_id: Uuid) -> Option<JobSnapshot> {
+        let jobs = self.jobs.lock().unwrap();
+        jobs.get(&job_id).map(|job| JobSnapshot {
+            job_id,
+            status: job.status,
+            progress: job.progress.snapshot(),
+            stats: job.stats.clone(),
+            error: job.error.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shutdown_cancels_unfinished_jobs_only() {
+        let shutdown = CancellationToken::new();
+        let registry = JobRegistry::new(shutdown.clone());
+        let (done_id, _, done_cancel) = registry.submit(1);
+        registry.finish(done_id, Ok(Vec::new()));
+        let (running_id, _, running_cancel) = registry.submit(1);
+        registry.start(running_id);
+
+        shutdown.cancel();
+
+        assert!(running_cancel.is_cancelled());
+        registry.finish(running_id, Ok(Vec::new()));
+        assert_eq!(registry.snapshot(running_id).unwrap().status, JobStatus::Cancelled);
+        assert_eq!(registry.snapshot(done_id).unwrap().status, JobStatus::Done);
+        // 已结束任务的令牌虽被级联取消，但状态不再变化
+        assert!(done_cancel.is_cancelled());
+    }
+
+    #[test]
+    fn cancelling_one_job_leaves_others_running() {
+        let registry = JobRegistry::new(CancellationToken::new());
+        let (first, _, first_cancel) = registry.submit(1);
+        let (_, _, second_cancel) = registry.submit(1);
+
+        assert_eq!(registry.cancel(first), Some(JobStatus::Queued));
+
+        assert!(first_cancel.is_cancelled());
+        assert!(!second_cancel.is_cancelled());
+        assert_eq!(registry.cancel(Uuid::new_v4()), None);
+    }
+}
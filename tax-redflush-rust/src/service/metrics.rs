@@ -0,0 +1,150 @@
+use crate::models::MatchStats;
+use bigdecimal::{BigDecimal, ToPrimitive, Zero};
+use metrics_exporter_prometheus::{BuildError, Matcher, PrometheusBuilder, PrometheusHandle};
+use std::time::Duration;
+
+/// 单据匹配耗时分桶（秒）
+const BILL_DURATION_BUCKETS: &[f64] = &[0.1, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0, 600.0];
+/// 单据使用发票数分桶
+const INVOICES_USED_BUCKETS: &[f64] = &[1.0, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0];
+/// 单据候选发票数分桶
+const CANDIDATE_INVOICES_BUCKETS: &[f64] = &[10.0, 100.0, 500.0, 1000.0, 5000.0, 10000.0, 50000.0, 100000.0];
+
+const UNMATCHED_GAP_AMOUNT: &str = "redblue_unmatched_gap_amount";
+const BILLS_WITH_GAPS_TOTAL: &str = "redblue_bills_with_gaps_total";
+const BILLS_MATCHED_TOTAL: &str = "redblue_bills_matched_total";
+const BILLS_FAILED_TOTAL: &str = "redblue_bills_failed_total";
+const ACTIVE_JOBS: &str = "redblue_active_jobs";
+const BILL_MATCH_DURATION_SECONDS: &str = "redblue_bill_match_duration_seconds";
+const BILL_INVOICES_USED: &str = "redblue_bill_invoices_used";
+const BILL_CANDIDATE_INVOICES: &str = "redblue_bill_candidate_invoices";
+
+/// 匹配指标的 Prometheus 导出器配置（直方图分桶）
+pub fn prometheus_builder() -> Result<PrometheusBuilder, BuildError> {
+    PrometheusBuilder::new()
+        .set_buckets_for_metric(Matcher::Full(BILL_MATCH_DURATION_SECONDS.to_string()), BILL_DURATION_BUCKETS)?
+        .set_buckets_for_metric(Matcher::Full(BILL_INVOICES_USED.to_string()), INVOICES_USED_BUCKETS)?
+        .set_buckets_for_metric(Matcher::Full(BILL_CANDIDATE_INVOICES.to_string()), CANDIDATE_INVOICES_BUCKETS)
+}
+
+/// 安装全局 Prometheus 记录器并登记指标说明，返回用于渲染 `/metrics` 的句柄
+///
+/// 未安装记录器时（如命令行工具）记录指标为空操作。
+pub fn install_recorder() -> Result<PrometheusHandle, BuildError> {
+    let handle = prometheus_builder()?.install_recorder()?;
+    describe();
+    Ok(handle)
+}
+
+/// 登记指标说明（渲染为 `# HELP`）
+pub fn describe() {
+    metrics::describe_gauge!(
+        UNMATCHED_GAP_AMOUNT,
+        "Total unmatched gap amount of the last batch (f64, approximate)"
+    );
+    metrics::describe_counter!(BILLS_WITH_GAPS_TOTAL, "Bills that finished matching with unmatched gaps");
+    metrics::describe_counter!(BILLS_MATCHED_TOTAL, "Bills that finished matching successfully");
+    metrics::describe_counter!(BILLS_FAILED_TOTAL, "Bills whose matching failed with an error");
+    metrics::describe_gauge!(ACTIVE_JOBS, "Async match jobs that are queued or running");
+    metrics::describe_histogram!(BILL_MATCH_DURATION_SECONDS, "Time to match and export a single bill");
+    metrics::describe_histogram!(BILL_INVOICES_USED, "Invoices used per matched bill");
+    metrics::describe_histogram!(BILL_CANDIDATE_INVOICES, "Candidate invoices loaded per matched bill");
+}
+
+/// 单个单据匹配结束时更新指标（`stats` 为 None 表示匹配失败）
+pub fn record_bill(elapsed: Duration, stats: Option<&MatchStats>) {
+    metrics::histogram!(BILL_MATCH_DURATION_SECONDS).record(elapsed.as_secs_f64());
+    match stats {
+        Some(stats) => {
+            metrics::counter!(BILLS_MATCHED_TOTAL).increment(1);
+            if !stats.skipped_existing {
+                metrics::histogram!(BILL_INVOICES_USED).record(stats.invoices_used as f64);
+                metrics::histogram!(BILL_CANDIDATE_INVOICES).record(stats.total_candidate_invoices as f64);
+            }
+        }
+        None => metrics::counter!(BILLS_FAILED_TOTAL).increment(1),
+    }
+}
+
+/// 批量匹配结束时更新指标
+///
+/// 缺口金额以 f64 上报：BigDecimal 转 f64 超过约 15 位有效数字时会丢失精度，
+/// 仅用于监控告警，对账请以缺口报告中的精确金额为准。
+pub fn record_batch(all_stats: &[MatchStats]) {
+    let mut gap_amount = BigDecimal::zero();
+    let mut bills_with_gaps = 0;
+    for stats in all_stats {
+        if stats.total_gap_amount > BigDecimal::zero() {
+            gap_amount += &stats.total_gap_amount;
+            bills_with_gaps += 1;
+        }
+    }
+
+    metrics::gauge!(UNMATCHED_GAP_AMOUNT).set(gap_amount.to_f64().unwrap_or(f64::MAX));
+    metrics::counter!(BILLS_WITH_GAPS_TOTAL).increment(bills_with_gaps);
+}
+
+/// 抓取时更新未结束的异步任务数
+pub fn record_active_jobs(active_jobs: usize) {
+    metrics::gauge!(ACTIVE_JOBS).set(active_jobs as f64);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn stats_with_gap(bill_id: i64, gap: &str) -> MatchStats {
+        MatchStats { bill_id, total_gap_amount: BigDecimal::from_str(gap).unwrap(), ..MatchStats::default() }
+    }
+
+    /// 在局部记录器下执行 f，返回渲染结果
+    fn render_with(f: impl FnOnce()) -> String {
+        let recorder = prometheus_builder().unwrap().build_recorder();
+        let handle = recorder.handle();
+        metrics::with_local_recorder(&recorder, || {
+            describe();
+            f();
+        });
+        handle.render()
+    }
+
+    #[test]
+    fn gap_gauge_reflects_seeded_gap() {
+        let rendered = render_with(|| {
+            record_batch(&[stats_with_gap(1, "12.5"), stats_with_gap(2, "0"), stats_with_gap(3, "7.25")]);
+        });
+
+        assert!(rendered.contains("\nredblue_unmatched_gap_amount 19.75\n"));
+        assert!(rendered.contains("\nredblue_bills_with_gaps_total 2\n"));
+        assert!(rendered.contains("# HELP redblue_bills_with_gaps_total Bills that finished matching with unmatched gaps"));
+    }
+
+    #[test]
+    fn gap_gauge_tracks_last_batch_while_counter_accumulates() {
+        let rendered = render_with(|| {
+            record_batch(&[stats_with_gap(1, "12.5")]);
+            record_batch(&[stats_with_gap(2, "0")]);
+        });
+
+        assert!(rendered.contains("\nredblue_unmatched_gap_amount 0\n"));
+        assert!(rendered.contains("\nredblue_bills_with_gaps_total 1\n"));
+    }
+
+    #[test]
+    fn bill_histograms_use_configured_buckets() {
+        let stats = MatchStats { invoices_used: 3, total_candidate_invoices: 40, ..MatchStats::default() };
+
+        let rendered = render_with(|| {
+            record_bill(Duration::from_millis(300), Some(&stats));
+            record_bill(Duration::from_secs(2), None);
+        });
+
+        assert!(rendered.contains("redblue_bill_match_duration_seconds_bucket{le=\"0.5\"} 1\n"));
+        assert!(rendered.contains("redblue_bill_match_duration_seconds_count 2\n"));
+        assert!(rendered.contains("redblue_bill_invoices_used_bucket{le=\"5\"} 1\n"));
+        assert!(rendered.contains("redblue_bill_candidate_invoices_bucket{le=\"100\"} 1\n"));
+        assert!(rendered.contains("\nredblue_bills_matched_total 1\n"));
+        assert!(rendered.contains("\nredblue_bills_failed_total 1\n"));
+    }
+}
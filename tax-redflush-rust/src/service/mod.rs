@@ -1,5 +1,27 @@
+pub mod annotator;
+pub mod bill_ids;
+pub mod bill_lock;
+pub mod candidate_cache;
+pub mod cancel;
+pub mod compare;
+pub mod in_flight;
+pub mod jobs;
 pub mod matcher;
 pub mod matcher_invoice_centric;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod progress;
+pub mod tax_pair_throttle;
 
-pub use matcher::MatcherService;
-pub use matcher_invoice_centric::InvoiceCentricMatcher;
+pub use annotator::{NoopAnnotator, ResultAnnotator};
+pub use bill_ids::{parse_bill_ids, InvalidBillIdLine, ParsedBillIds};
+pub use bill_lock::BillLockRegistry;
+pub use candidate_cache::CandidateCache;
+pub use cancel::CancellationToken;
+pub use compare::compare_invoice_overlap;
+pub use in_flight::{InFlightGuard, InFlightTracker};
+pub use jobs::{JobRegistry, JobSnapshot, JobStatus};
+pub use matcher::{MatcherService, SkuBillOutcome};
+pub use matcher_invoice_centric::{BillMatchOutcome, CandidateCoverage, CandidateQueryPlans, CandidateSet, CandidateSummary, InvoiceCentricMatcher, MatchControl};
+pub use progress::{BatchProgress, ProgressEvent, ProgressSnapshot};
+pub use tax_pair_throttle::TaxPairThrottle;
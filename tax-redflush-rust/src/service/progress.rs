@@ -0,0 +1,110 @@
+use serde::Serialize;
+use std::sync::atomic::{AtomicI64, AtomicUsize, Ordering};
+
+/// 当前单据 ID 的空值哨兵
+const NO_BILL: i64 = i64::MIN;
+
+/// 同步批量匹配的进度计数器
+///
+/// 仅跟踪当前正在运行的一批：每次批量开始时重置，每完成一个单据递增。
+#[derive(Debug)]
+pub struct BatchProgress {
+    current: AtomicUsize,
+    total: AtomicUsize,
+    current_bill_id: AtomicI64,
+}
+
+/// 单据匹配迭代进度事件（每轮选中发票时推送），供 SSE 接口输出
+#[derive(Debug, Clone, Serialize)]
+pub struct ProgressEvent {
+    pub bill_id: i64,
+    pub iteration: usize,
+    pub invoices_used: usize,
+    pub remaining_sku_count: usize,
+}
+
+/// 进度快照，供 `GET /api/match/progress` 返回
+#[derive(Debug, Clone, Serialize)]
+pub struct ProgressSnapshot {
+    pub current: usize,
+    pub total: usize,
+    pub current_bill_id: Option<i64>,
+}
+
+impl Default for BatchProgress {
+    fn default() -> Self {
+        Self {
+            current: AtomicUsize::new(0),
+            total: AtomicUsize::new(0),
+            current_bill_id: AtomicI64::new(NO_BILL),
+        }
+    }
+}
+
+impl BatchProgress {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 开始新一批匹配，重置计数
+    pub fn reset(&self, total: usize) {
+        self.current.store(0, Ordering::SeqCst);
+        self.total.store(total, Ordering::SeqCst);
+        self.current_bill_id.store(NO_BILL, Ordering::SeqCst);
+    }
+
+    /// 标记开始处理某个单据（并发匹配时记录最近开始的单据）
+    pub fn start_bill(&self, bill_id: i64) {
+        self.current_bill_id.store(bill_id, Ordering::SeqCst);
+    }
+
+    /// 标记一个单据处理完成
+    pub fn finish_bill(&self) {
+        self.current.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn snapshot(&self) -> ProgressSnapshot {
+        let bill_id = self.current_bill_id.load(Ordering::SeqCst);
+        ProgressSnapshot {
+            current: self.current.load(Ordering::SeqCst),
+            total: self.total.load(Ordering::SeqCst),
+            current_bill_id: (bill_id != NO_BILL).then_some(bill_id),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counter_advances_as_bills_complete() {
+        let progress = BatchProgress::new();
+        progress.reset(3);
+        assert_eq!(progress.snapshot().current, 0);
+        assert_eq!(progress.snapshot().current_bill_id, None);
+
+        for (done, bill_id) in [101, 102, 103].into_iter().enumerate() {
+            progress.start_bill(bill_id);
+            assert_eq!(progress.snapshot().current_bill_id, Some(bill_id));
+            assert_eq!(progress.snapshot().current, done);
+            progress.finish_bill();
+        }
+
+        let snapshot = progress.snapshot();
+        assert_eq!((snapshot.current, snapshot.total), (3, 3));
+    }
+
+    #[test]
+    fn reset_starts_a_new_batch() {
+        let progress = BatchProgress::new();
+        progress.reset(2);
+        progress.start_bill(101);
+        progress.finish_bill();
+
+        progress.reset(5);
+
+        let snapshot = progress.snapshot();
+        assert_eq!((snapshot.current, snapshot.total, snapshot.current_bill_id), (0, 5, None));
+    }
+}
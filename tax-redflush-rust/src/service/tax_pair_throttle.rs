@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// 按销购方税号对限流 - 同一税号对同时访问数据库的单据数不超过 K
+///
+/// 热点税号对的大量单据并发查询时容易在共享 Postgres 上产生锁竞争，
+/// 不同税号对之间互不影响。
+#[derive(Debug)]
+pub struct TaxPairThrottle {
+    /// 每个税号对的并发上限 (None 表示不限流)
+    limit: Option<usize>,
+    semaphores: Mutex<HashMap<(String, String), Arc<Semaphore>>>,
+}
+
+impl TaxPairThrottle {
+    pub fn new(limit: Option<usize>) -> Self {
+        Self {
+            limit: limit.filter(|&k| k > 0),
+            semaphores: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 获取税号对的许可，持有返回的 permit 期间占用一个并发名额；未启用限流时返回 None
+    pub async fn acquire(&self, buyer_tax_no: &str, seller_tax_no: &str) -> Option<OwnedSemaphorePermit> {
+        let limit = self.limit?;
+        let semaphore = {
+            let mut semaphores = self.semaphores.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            // 顺带清理空闲的信号量，避免表无限增长
+            semaphores.retain(|_, semaphore| Arc::strong_count(semaphore) > 1);
+            semaphores
+                .entry((buyer_tax_no.to_string(), seller_tax_no.to_string()))
+                .or_insert_with(|| Arc::new(Semaphore::new(limit)))
+                .clone()
+        };
+
+        if semaphore.available_permits() == 0 {
+            tracing::info!(
+                "税号对 {}/{} 已有 {} 个单据在查询, 等待空闲名额...",
+                buyer_tax_no, seller_tax_no, limit
+            );
+        }
+
+        // 信号量从不关闭，acquire_owned 不会失败
+        semaphore.acquire_owned().await.ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    /// 模拟一次单据查询: 持有许可期间记录并发数
+    async fn query_once(throttle: &TaxPairThrottle, seller_tax_no: &str, active: &AtomicUsize, peak: &AtomicUsize) {
+        let _permit = throttle.acquire("BUYER", seller_tax_no).await;
+        let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+        peak.fetch_max(now, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        active.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn same_tax_pair_is_throttled_to_limit() {
+        let throttle = TaxPairThrottle::new(Some(2));
+        let (active, peak) = (AtomicUsize::new(0), AtomicUsize::new(0));
+
+        tokio::join!(
+            query_once(&throttle, "SELLER", &active, &peak),
+            query_once(&throttle, "SELLER", &active, &peak),
+            query_once(&throttle, "SELLER", &active, &peak),
+            query_once(&throttle, "SELLER", &active, &peak),
+        );
+
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn different_tax_pairs_run_in_parallel() {
+        let throttle = TaxPairThrottle::new(Some(1));
+        let (active, peak) = (AtomicUsize::new(0), AtomicUsize::new(0));
+
+        tokio::join!(
+            query_once(&throttle, "SELLER_A", &active, &peak),
+            query_once(&throttle, "SELLER_B", &active, &peak),
+            query_once(&throttle, "SELLER_C", &active, &peak),
+        );
+
+        assert_eq!(peak.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn disabled_throttle_hands_out_no_permits() {
+        assert!(TaxPairThrottle::new(None).acquire("BUYER", "SELLER").await.is_none());
+        assert!(TaxPairThrottle::new(Some(0)).acquire("BUYER", "SELLER").await.is_none());
+    }
+}